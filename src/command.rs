@@ -1,23 +1,31 @@
 use std::any::Any;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use failure::format_err;
 use regex::Regex;
 
+use crate::endpoints::*;
 use crate::http_client::HttpClient;
+use crate::interrupt;
 use crate::model::*;
 use crate::parser::*;
 use crate::result::Result;
+use crate::summary::Summary;
 use crate::udemy_helper::*;
 
 pub struct CommandContext<'a> {
     pub course_name: String,
     pub portal_name: String,
+    pub user: Option<User>,
     pub course: Option<Course>,
     pub course_content: Option<CourseContent>,
     pub auth: Auth,
+    pub endpoints: Endpoints,
     pub parser: &'a dyn Parser,
     pub client: &'a dyn HttpClient,
     pub udemy_helper: &'a UdemyHelper<'a>,
+    pub interrupted: &'a AtomicBool,
 }
 
 impl<'a> CommandContext<'a> {
@@ -28,38 +36,145 @@ impl<'a> CommandContext<'a> {
         udemy_helper: &'a UdemyHelper,
         auth: Auth,
     ) -> Result<CommandContext<'a>> {
-        let re = Regex::new(
-            r"(?i)(?://(?P<portal_name>.+?).udemy.com/course/(?P<course_name>[a-zA-Z0-9_-]+))/?.*",
-        )?;
-        let captures = re
-            .captures(url)
-            .ok_or_else(|| format_err!("Could not parse provide url <{}>", url))?;
-        let course_name = String::from(
-            captures
-                .name("course_name")
-                .ok_or_else(|| format_err!("Could not compute course name out of url <{}>", url))?
-                .as_str(),
-        );
-        let portal_name = String::from(
-            captures
-                .name("portal_name")
-                .ok_or_else(|| format_err!("Could not compute portal name out of url <{}>", url))?
-                .as_str(),
-        );
+        let (portal_name, course_name) = parse_course_url(url)?;
         Ok(CommandContext {
+            course_name,
+            ..CommandContext::for_portal(portal_name.as_str(), client, parser, udemy_helper, auth)
+        })
+    }
+
+    /// Context of commands about the account only, without a course.
+    pub fn for_portal(
+        portal_name: &str,
+        client: &'a dyn HttpClient,
+        parser: &'a dyn Parser,
+        udemy_helper: &'a UdemyHelper,
+        auth: Auth,
+    ) -> CommandContext<'a> {
+        CommandContext {
+            user: None,
             course: None,
             course_content: None,
-            course_name,
-            portal_name,
+            endpoints: Endpoints::new(DEFAULT_API_BASE, portal_name),
+            course_name: String::new(),
+            portal_name: portal_name.into(),
             client,
             parser,
             udemy_helper,
             auth,
-        })
+            interrupted: interrupt::flag(),
+        }
+    }
+
+    /// Use another base url for the api, `{portal}` being replaced by the portal name.
+    pub fn with_api_base(mut self, api_base: &str) -> CommandContext<'a> {
+        self.endpoints = Endpoints::new(api_base, self.portal_name.as_str());
+        self
+    }
+
+    /// Use another flag than the one set on Ctrl-C to ask the command to stop.
+    #[cfg(test)]
+    pub fn with_interrupted(mut self, interrupted: &'a AtomicBool) -> CommandContext<'a> {
+        self.interrupted = interrupted;
+        self
+    }
+
+    /// Tell whether the user asked to stop, so that no new work is to be started.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    fn course_id(&self) -> Result<CourseId> {
+        Ok(self
+            .course
+            .as_ref()
+            .ok_or_else(|| format_err!("Course should be known to query its lectures"))?
+            .id)
+    }
+
+    /// Get the details of a lecture, including its download urls.
+    pub fn get_lecture_detail(&self, lecture: &Lecture) -> Result<LectureDetail> {
+        let url = self.endpoints.lecture(self.course_id()?, lecture.id);
+        let lecture_detail = self.client.get_as_json(url.as_str(), &self.auth)?;
+        self.parser.parse_lecture_detail(&lecture_detail)
+    }
+
+    /// Get the download urls of a supplementary asset of a lecture.
+    pub fn get_supplementary_asset_urls(
+        &self,
+        lecture: &Lecture,
+        asset: &SupplementaryAsset,
+    ) -> Result<Vec<DownloadUrl>> {
+        let url = self
+            .endpoints
+            .supplementary_asset(self.course_id()?, lecture.id, asset.id);
+        let asset = self.client.get_as_json(url.as_str(), &self.auth)?;
+        self.parser.parse_supplementary_asset(&asset)
+    }
+
+    /// Mark a lecture as completed, telling whether it was downloaded.
+    pub fn mark_lecture_completed(&self, lecture: &Lecture, downloaded: bool) -> Result<()> {
+        let url = self.endpoints.completed_lectures(self.course_id()?);
+        let complete_request = CompleteRequest {
+            lecture_id: lecture.id,
+            downloaded,
+        };
+        let value = serde_json::to_value(complete_request)?;
+        self.client.post_json(url.as_str(), &value, &self.auth)
+    }
+
+    /// Mark a lecture as not completed.
+    pub fn unmark_lecture_completed(&self, lecture: &Lecture) -> Result<()> {
+        let url = self
+            .endpoints
+            .completed_lecture(self.course_id()?, lecture.id);
+        self.client.delete(url.as_str(), &self.auth)
+    }
+
+    /// Get the ids of the lectures of the course already completed by the user.
+    pub fn get_completed_lecture_ids(&self) -> Result<HashSet<LectureId>> {
+        let url = self.endpoints.progress(self.course_id()?);
+        let progress = self.client.get_as_json(url.as_str(), &self.auth)?;
+        Ok(self
+            .parser
+            .parse_completed_lecture_ids(&progress)?
+            .into_iter()
+            .collect())
     }
 }
 
+/// Extract the portal name and the course name out of a course url.
+pub fn parse_course_url(url: &str) -> Result<(String, String)> {
+    let re = Regex::new(
+        r"(?i)(?://(?P<portal_name>.+?).udemy.com/course/(?P<course_name>[a-zA-Z0-9_-]+))/?.*",
+    )?;
+    let captures = re
+        .captures(url)
+        .ok_or_else(|| format_err!("Could not parse provide url <{}>", url))?;
+    let course_name = String::from(
+        captures
+            .name("course_name")
+            .ok_or_else(|| format_err!("Could not compute course name out of url <{}>", url))?
+            .as_str(),
+    );
+    let portal_name = String::from(
+        captures
+            .name("portal_name")
+            .ok_or_else(|| format_err!("Could not compute portal name out of url <{}>", url))?
+            .as_str(),
+    );
+    Ok((portal_name, course_name))
+}
+
 pub trait Command {
     fn set_params(&mut self, params: &dyn Any);
-    fn execute(&self, command_context: &CommandContext) -> Result<()>;
+    fn execute(&self, command_context: &CommandContext) -> Result<Summary>;
+    /// Whether the course information has to be retrieved before executing.
+    fn needs_course_info(&self) -> bool {
+        true
+    }
+    /// Whether the command shows the authenticated user by itself.
+    fn shows_user(&self) -> bool {
+        false
+    }
 }
@@ -1,19 +1,26 @@
 use std::any::Any;
+use std::collections::HashSet;
+use std::time::Instant;
 
 use failure::format_err;
 
 use crate::command::*;
 use crate::model::*;
 use crate::result::Result;
+use crate::selection::Selection;
+use crate::summary::*;
+use crate::utils::elapsed_millis;
 
 pub struct CompleteParams {
-    pub wanted_chapter: ObjectIndex,
-    pub wanted_lecture: Option<LectureId>,
+    pub wanted_chapters: Option<Selection>,
+    pub wanted_lectures: Option<Selection>,
+    pub undo: bool,
+    pub dry_run: bool,
     pub verbose: bool,
 }
 
-/// Complete files to a specified location. It is possible to specify
-/// which chapter / lecture to download.
+/// Mark lectures as completed, or as not completed when undoing. It is possible
+/// to specify which chapters / lectures to mark, the whole course otherwise.
 pub struct Complete {
     params: Option<CompleteParams>,
 }
@@ -28,22 +35,18 @@ impl Command for Complete {
     fn set_params(&mut self, params: &dyn Any) {
         if let Some(params) = params.downcast_ref::<CompleteParams>() {
             self.params = Some(CompleteParams {
-                wanted_chapter: params.wanted_chapter,
-                wanted_lecture: params.wanted_lecture,
+                wanted_chapters: params.wanted_chapters.clone(),
+                wanted_lectures: params.wanted_lectures.clone(),
+                undo: params.undo,
+                dry_run: params.dry_run,
                 verbose: params.verbose,
             });
         }
     }
 
-    fn execute(&self, context: &CommandContext) -> Result<()> {
+    fn execute(&self, context: &CommandContext) -> Result<Summary> {
         if let Some(params) = self.params.as_ref() {
-            self.complete(
-                context,
-                params.wanted_chapter,
-                params.wanted_lecture,
-                params.verbose,
-            )?;
-            Ok(())
+            self.complete(context, params)
         } else {
             Err(format_err!(
                 "Params should be populated for executing command"
@@ -57,32 +60,75 @@ impl Complete {
         &self,
         context: &CommandContext,
         chapter: &Chapter,
-        wanted_lecture: Option<ObjectIndex>,
-        verbose: bool,
+        params: &CompleteParams,
+        completed: &HashSet<LectureId>,
+        summary: &mut Summary,
     ) -> Result<()> {
-        if verbose {
+        if params.verbose {
             println!(
-                "Completing chapter {} - {}",
-                chapter.object_index, chapter.title
+                "{} chapter {} - {}",
+                if params.undo {
+                    "Uncompleting"
+                } else {
+                    "Completing"
+                },
+                chapter.object_index,
+                chapter.title
             );
         }
         chapter
             .lectures
             .iter()
             .filter(|lecture| {
-                wanted_lecture.is_none() || wanted_lecture.unwrap() == lecture.object_index
+                params
+                    .wanted_lectures
+                    .as_ref()
+                    .is_none_or(|wanted| wanted.contains(lecture.object_index))
             })
-            .for_each(move |lecture| {
-                match self.complete_lecture(context, &lecture, verbose) {
-                    Ok(_) => {
-                        // if verbose {
-                        //     println!("Lecture downloaded");
-                        // }
-                    }
-                    Err(e) => {
-                        eprintln!("Error while completing {}: {}", lecture.title, e);
+            .take_while(|_| !context.is_interrupted())
+            .for_each(|lecture| {
+                let start = Instant::now();
+                let mut outcome = if params.undo && !completed.contains(&lecture.id) {
+                    LectureOutcome::new(chapter, lecture, Status::Skipped)
+                        .with_reason("not completed")
+                } else if !params.undo && completed.contains(&lecture.id) {
+                    LectureOutcome::new(chapter, lecture, Status::Skipped)
+                        .with_reason("already completed")
+                } else if params.dry_run {
+                    println!(
+                        "Would {} lecture {} - {}",
+                        if params.undo {
+                            "uncomplete"
+                        } else {
+                            "complete"
+                        },
+                        lecture.object_index,
+                        lecture.title
+                    );
+                    LectureOutcome::new(chapter, lecture, Status::Skipped).with_reason("dry run")
+                } else {
+                    let (result, status) = if params.undo {
+                        (
+                            self.uncomplete_lecture(context, lecture, params.verbose),
+                            Status::Uncompleted,
+                        )
+                    } else {
+                        (
+                            self.complete_lecture(context, lecture, params.verbose),
+                            Status::Completed,
+                        )
+                    };
+                    match result {
+                        Ok(_) => LectureOutcome::new(chapter, lecture, status),
+                        Err(e) => {
+                            eprintln!("Error while marking {}: {}", lecture.title, e);
+                            LectureOutcome::new(chapter, lecture, Status::Failed)
+                                .with_reason(e.to_string().as_str())
+                        }
                     }
                 };
+                outcome.duration_ms = elapsed_millis(start);
+                summary.push(outcome);
             });
         Ok(())
     }
@@ -96,43 +142,45 @@ impl Complete {
         if verbose {
             println!("Completing lecture {}", lecture.title);
         }
-        let url = format!(
-            "https://{portal_name}.udemy.com/api-2.0/users/me/subscribed-courses/{course_id}/completed-lectures/",
-            portal_name = context.portal_name,
-            course_id = context.course.as_ref().unwrap().id
-        );
-        let complete_request = CompleteRequest {
-            lecture_id: lecture.id,
-            downloaded: false,
-        };
-        let value = serde_json::to_value(complete_request)?;
-        context
-            .client
-            .post_json(url.as_str(), &value, &context.auth)?;
-        Ok(())
+        context.mark_lecture_completed(lecture, false)
     }
 
-    /// Complete chapters and lectures.
-    pub fn complete(
+    fn uncomplete_lecture(
         &self,
         context: &CommandContext,
-        wanted_chapter: ObjectIndex,
-        wanted_lecture: Option<LectureId>,
+        lecture: &Lecture,
         verbose: bool,
     ) -> Result<()> {
         if verbose {
+            println!("Uncompleting lecture {}", lecture.title);
+        }
+        context.unmark_lecture_completed(lecture)
+    }
+
+    /// Complete or uncomplete chapters and lectures.
+    pub fn complete(&self, context: &CommandContext, params: &CompleteParams) -> Result<Summary> {
+        if params.verbose {
             println!(
-                "Complete chapter: {}, lecture: {:?}",
-                wanted_chapter, wanted_lecture
+                "Complete chapters: {:?}, lectures: {:?}, undo: {}",
+                params.wanted_chapters, params.wanted_lectures, params.undo
             );
         }
 
+        let completed = context.get_completed_lecture_ids()?;
+        let mut summary = Summary::new();
         for chapter in context.course_content.as_ref().unwrap().chapters.iter() {
-            if wanted_chapter == chapter.object_index {
-                self.complete_chapter(context, &chapter, wanted_lecture, verbose)?;
+            if context.is_interrupted() {
+                break;
+            }
+            if params
+                .wanted_chapters
+                .as_ref()
+                .is_none_or(|wanted| wanted.contains(chapter.object_index))
+            {
+                self.complete_chapter(context, &chapter, params, &completed, &mut summary)?;
             }
         }
-        Ok(())
+        Ok(summary)
     }
 }
 
@@ -146,6 +194,7 @@ mod test {
 
     #[test]
     fn complete() {
+        let _mocks = lock_mocks();
         unsafe {
             PARSE = Some(vec![]);
             GETS_AS_JSON_URL = Some(vec![]);
@@ -153,6 +202,7 @@ mod test {
             GETS_AS_DATA_URL = Some(vec![]);
             POST_JSON_DATA_URL = Some(vec![]);
             POST_JSON_DATA_BODY = Some(vec![]);
+            COMPLETED_LECTURE_IDS = None;
         }
 
         let fs_helper = MockFsHelper {};
@@ -176,14 +226,17 @@ mod test {
 
         let mut complete = Complete::new();
         complete.set_params(&CompleteParams {
-            wanted_chapter: 1,
-            wanted_lecture: Some(1),
+            wanted_chapters: Some("1".parse().unwrap()),
+            wanted_lectures: Some("1".parse().unwrap()),
+            undo: false,
+            dry_run: false,
             verbose: false,
         });
 
         let result = complete.execute(&context);
 
         assert!(result.is_ok());
+        assert_eq!(result.unwrap().count(Status::Completed), 1);
 
         unsafe {
             if let Some(ref pjd) = POST_JSON_DATA_URL {
@@ -199,4 +252,111 @@ mod test {
 
     #[test]
     fn complte() {}
+
+    fn run_complete(params: CompleteParams, completed: Vec<LectureId>) -> Result<Summary> {
+        unsafe {
+            GETS_AS_JSON_URL = Some(vec![]);
+            POST_JSON_DATA_URL = Some(vec![]);
+            POST_JSON_DATA_BODY = Some(vec![]);
+            DELETE_URL = Some(vec![]);
+            COMPLETED_LECTURE_IDS = Some(completed);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut complete = Complete::new();
+        complete.set_params(&params);
+        complete.execute(&context)
+    }
+
+    #[test]
+    fn complete_whole_course_skips_completed() {
+        let _mocks = lock_mocks();
+        let result = run_complete(
+            CompleteParams {
+                wanted_chapters: None,
+                wanted_lectures: None,
+                undo: false,
+                dry_run: false,
+                verbose: false,
+            },
+            vec![4321],
+        );
+
+        assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.count(Status::Skipped), 1);
+        assert_eq!(summary.outcomes[0].reason, Some("already completed".into()));
+        unsafe {
+            if let Some(ref pjd) = POST_JSON_DATA_URL {
+                assert!(pjd.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn complete_undo() {
+        let _mocks = lock_mocks();
+        let result = run_complete(
+            CompleteParams {
+                wanted_chapters: Some("1-2".parse().unwrap()),
+                wanted_lectures: None,
+                undo: true,
+                dry_run: false,
+                verbose: false,
+            },
+            vec![4321],
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().count(Status::Uncompleted), 1);
+        unsafe {
+            if let Some(ref du) = DELETE_URL {
+                assert_eq!(du.len(), 1);
+                assert_eq!(du[0], "https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/completed-lectures/4321/");
+            }
+        }
+    }
+
+    #[test]
+    fn complete_dry_run() {
+        let _mocks = lock_mocks();
+        let result = run_complete(
+            CompleteParams {
+                wanted_chapters: None,
+                wanted_lectures: Some("1,3".parse().unwrap()),
+                undo: false,
+                dry_run: true,
+                verbose: false,
+            },
+            vec![],
+        );
+
+        assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.count(Status::Skipped), 1);
+        assert_eq!(summary.outcomes[0].reason, Some("dry run".into()));
+        unsafe {
+            if let Some(ref pjd) = POST_JSON_DATA_URL {
+                assert!(pjd.is_empty());
+            }
+        }
+    }
 }
@@ -0,0 +1,195 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::format_err;
+use reqwest::header::HeaderValue;
+
+use crate::error::UdemyError;
+use crate::model::{Auth, Cookie};
+use crate::result::Result;
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+const ACCESS_TOKEN: &str = "access_token";
+
+/// Parse the content of a Netscape formatted cookie file (cookies.txt).
+///
+/// Each non comment line holds 7 tab separated fields: domain, include subdomains,
+/// path, secure, expiry, name and value. Lines prefixed with `#HttpOnly_` are
+/// regular cookies flagged as http only by the exporting browser. Path and secure
+/// are not retained as cookies are only sent on https api calls.
+pub fn parse_cookies_txt(content: &str) -> Result<Vec<Cookie>> {
+    let mut cookies = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let line = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(format_err!(
+                "Invalid cookie line {}: expected 7 tab separated fields, found {}",
+                index + 1,
+                fields.len()
+            ));
+        }
+        let expires = fields[4]
+            .parse::<u64>()
+            .map_err(|_| format_err!("Invalid cookie expiry on line {}", index + 1))?;
+        cookies.push(Cookie {
+            domain: fields[0].into(),
+            include_subdomains: fields[1].eq_ignore_ascii_case("TRUE"),
+            expires,
+            name: fields[5].into(),
+            value: fields[6].into(),
+        });
+    }
+    Ok(cookies)
+}
+
+/// Keep only the cookies that would be sent by a browser to host and that are not
+/// expired at time now (seconds since epoch). Session cookies (expiry 0) are kept.
+pub fn cookies_for_host(cookies: Vec<Cookie>, host: &str, now: u64) -> Vec<Cookie> {
+    let host = host.to_lowercase();
+    cookies
+        .into_iter()
+        .filter(|cookie| cookie.expires == 0 || cookie.expires > now)
+        .filter(|cookie| {
+            let domain = cookie.domain.to_lowercase();
+            let subdomains = cookie.include_subdomains || domain.starts_with('.');
+            let domain = domain.trim_start_matches('.');
+            host == domain || (subdomains && host.ends_with(format!(".{}", domain).as_str()))
+        })
+        .collect()
+}
+
+/// Check that cookies can be sent in a `Cookie` header, which only takes
+/// visible ascii characters.
+fn check_cookies(cookies: &[Cookie], path: &str) -> Result<()> {
+    for cookie in cookies {
+        let pair = format!("{}={}", cookie.name, cookie.value);
+        if HeaderValue::from_str(pair.as_str()).is_err() {
+            return Err(UdemyError::Authentication {
+                message: format!(
+                    "Cookie {} of <{}> holds characters that cannot be sent to udemy",
+                    cookie.name, path
+                ),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Build authentication out of a cookie file for a given udemy portal. The bearer
+/// token is taken from the `access_token` cookie unless one is given explicitly.
+pub fn load_auth(path: &str, portal_name: &str, access_token: Option<&str>) -> Result<Auth> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format_err!("Could not read cookie file <{}>: {}", path, e))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let host = format!("{}.udemy.com", portal_name);
+    let cookies = cookies_for_host(parse_cookies_txt(content.as_str())?, host.as_str(), now);
+    check_cookies(&cookies, path)?;
+    let access_token = match access_token {
+        Some(access_token) => String::from(access_token),
+        None => cookies
+            .iter()
+            .find(|cookie| cookie.name == ACCESS_TOKEN)
+            .map(|cookie| cookie.value.clone())
+            .ok_or_else(|| {
+                format_err!(
+                    "No valid {} cookie found for {} in <{}>",
+                    ACCESS_TOKEN,
+                    host,
+                    path
+                )
+            })?,
+    };
+    Ok(Auth {
+        access_token: Some(access_token),
+        cookies,
+    })
+}
+
+#[cfg(test)]
+mod test_cookies {
+    use super::*;
+
+    const COOKIES_TXT: &str = "# Netscape HTTP Cookie File\n\
+                               # This is a generated file! Do not edit.\n\
+                               \n\
+                               .udemy.com\tTRUE\t/\tTRUE\t0\taccess_token\tthe-token\n\
+                               #HttpOnly_.udemy.com\tTRUE\t/\tTRUE\t4102444800\tud_cache_user\t1234\n\
+                               acme.udemy.com\tFALSE\t/\tTRUE\t4102444800\tsessionid\tacme-session\n\
+                               .udemy.com\tTRUE\t/\tFALSE\t1000\texpired\tgone\n\
+                               .example.com\tTRUE\t/\tFALSE\t0\tother\tnope\n";
+
+    #[test]
+    fn parse_cookies() {
+        let actual = parse_cookies_txt(COOKIES_TXT);
+
+        assert!(actual.is_ok());
+        let cookies = actual.unwrap();
+        assert_eq!(cookies.len(), 5);
+        assert_eq!(cookies[0].name, "access_token");
+        assert_eq!(cookies[0].value, "the-token");
+        assert_eq!(cookies[1].domain, ".udemy.com");
+        assert_eq!(cookies[1].name, "ud_cache_user");
+        assert_eq!(cookies[1].expires, 4_102_444_800);
+        assert!(!cookies[2].include_subdomains);
+    }
+
+    #[test]
+    fn parse_cookies_invalid_line() {
+        let actual = parse_cookies_txt(".udemy.com\tTRUE\t/\n");
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn check_cookies_with_control_character() {
+        let mut cookies = parse_cookies_txt(COOKIES_TXT).unwrap();
+        assert!(check_cookies(&cookies, "cookies.txt").is_ok());
+
+        cookies[2].value = "acme\u{1}session".into();
+        let actual = check_cookies(&cookies, "cookies.txt");
+
+        assert_eq!(
+            actual.unwrap_err().to_string(),
+            "Cookie sessionid of <cookies.txt> holds characters that cannot be sent to udemy"
+        );
+    }
+
+    #[test]
+    fn cookies_for_business_portal() {
+        let cookies = parse_cookies_txt(COOKIES_TXT).unwrap();
+
+        let actual = cookies_for_host(cookies, "acme.udemy.com", 2000);
+
+        assert_eq!(
+            actual
+                .iter()
+                .map(|cookie| cookie.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["access_token", "ud_cache_user", "sessionid"]
+        );
+    }
+
+    #[test]
+    fn cookies_for_www_portal() {
+        let cookies = parse_cookies_txt(COOKIES_TXT).unwrap();
+
+        let actual = cookies_for_host(cookies, "www.udemy.com", 2000);
+
+        assert_eq!(
+            actual
+                .iter()
+                .map(|cookie| cookie.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["access_token", "ud_cache_user"]
+        );
+    }
+}
@@ -1,25 +1,52 @@
 #![allow(clippy::too_many_arguments)]
 
 use std::any::Any;
-use std::fs::File;
-use std::io::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use failure::format_err;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use reqwest::StatusCode;
 
 use crate::command::*;
+use crate::error::UdemyError;
+use crate::failures::*;
+use crate::http_client::HttpClient;
+use crate::lock::CourseLock;
 use crate::model::*;
+use crate::mp4::{extract_audio, write_tags, Mp4Tags};
+use crate::nfo::*;
+use crate::playlist::*;
+use crate::quality::{QualityPolicy, QualityRule};
 use crate::result::Result;
+use crate::summary::*;
 use crate::utils::*;
+use crate::viewer::*;
+
+/// Extension of files being downloaded or left incomplete.
+const PART_EXTENSION: &str = ".part";
+/// Number of times the url of a media may be refreshed while downloading it.
+const MAX_URL_REFRESHES: u32 = 3;
+
+/// Transformation of downloaded data before it is written.
+type PostProcess<'a> = &'a dyn Fn(&[u8]) -> Result<Vec<u8>>;
 
 pub struct DownloadParams {
     pub wanted_chapter: Option<ObjectIndex>,
     pub wanted_lecture: Option<LectureId>,
-    pub wanted_quality: Option<VideoQuality>,
+    pub wanted_quality: Option<QualityPolicy>,
     pub output: String,
     pub dry_run: bool,
     pub verbose: bool,
+    pub retry_failed: Option<String>,
+    pub mark_complete: bool,
+    pub force: bool,
+    pub max_size: Option<u64>,
+    pub audio_only: bool,
+    pub tag: bool,
+    pub nfo: bool,
+    pub html: bool,
 }
 
 /// Download files to a specified location. It is possible to specify
@@ -40,27 +67,42 @@ impl Command for Download {
             self.params = Some(DownloadParams {
                 wanted_chapter: params.wanted_chapter,
                 wanted_lecture: params.wanted_lecture,
-                wanted_quality: params.wanted_quality,
+                wanted_quality: params.wanted_quality.clone(),
                 output: params.output.clone(),
                 dry_run: params.dry_run,
                 verbose: params.verbose,
+                retry_failed: params.retry_failed.clone(),
+                mark_complete: params.mark_complete,
+                force: params.force,
+                max_size: params.max_size,
+                audio_only: params.audio_only,
+                tag: params.tag,
+                nfo: params.nfo,
+                html: params.html,
             });
         }
     }
 
-    fn execute(&self, context: &CommandContext) -> Result<()> {
+    fn execute(&self, context: &CommandContext) -> Result<Summary> {
         // self.print_course_content(&context.course_content.unwrap());
         if let Some(params) = self.params.as_ref() {
             self.download(
                 context,
                 params.wanted_chapter,
                 params.wanted_lecture,
-                params.wanted_quality,
+                &params.wanted_quality.clone().unwrap_or_default(),
                 params.output.as_str(),
                 params.dry_run,
                 params.verbose,
-            )?;
-            Ok(())
+                params.retry_failed.as_deref(),
+                params.mark_complete,
+                params.force,
+                params.max_size,
+                params.audio_only,
+                params.tag,
+                params.nfo,
+                params.html,
+            )
         } else {
             Err(format_err!(
                 "Params should be populated for executing command"
@@ -75,9 +117,17 @@ impl Download {
         context: &CommandContext,
         lecture_title: &str,
         url: &str,
+        refresh_url: &dyn Fn() -> Result<String>,
+        postprocess: Option<PostProcess>,
         target_filename: &str,
-    ) -> Result<()> {
-        let content_length = context.client.get_content_length(url)?;
+        verbose: bool,
+    ) -> Result<u64> {
+        let mut url = String::from(url);
+        let mut refreshes = 0;
+        let content_length =
+            self.with_fresh_url(&mut url, &mut refreshes, refresh_url, verbose, &mut |url| {
+                context.client.get_content_length(url)
+            })?;
         let start = Instant::now();
 
         let pb = ProgressBar::new(content_length);
@@ -87,13 +137,52 @@ impl Download {
                 .progress_chars("#>-"),
         );
         pb.set_message(lecture_title);
-        let buf = context.client.get_as_data(url, &mut |size| {
-            pb.set_position(size);
-        })?;
-        let mut file = File::create(target_filename)?;
-        let _size = file.write(&buf)?;
-        let elapsed = Instant::now().duration_since(start);
-        let elapsed = elapsed.as_secs() * 1000u64 + u64::from(elapsed.subsec_millis());
+        let mut buf = Vec::with_capacity(content_length as usize);
+        let result =
+            self.with_fresh_url(&mut url, &mut refreshes, refresh_url, verbose, &mut |url| {
+                context.client.get_as_data(url, &mut buf, &mut |size| {
+                    pb.set_position(size);
+                })
+            });
+        // Data goes to a .part file first, so that only complete files get the
+        // final name and interrupted downloads stay marked as incomplete.
+        let part_filename = format!("{}{}", target_filename, PART_EXTENSION);
+        if let Err(e) = result {
+            pb.finish_and_clear();
+            if !buf.is_empty() {
+                context
+                    .udemy_helper
+                    .write_file(part_filename.as_str(), &buf)?;
+            }
+            return Err(e);
+        }
+        if buf.len() as u64 != content_length {
+            pb.finish_and_clear();
+            context
+                .udemy_helper
+                .write_file(part_filename.as_str(), &buf)?;
+            return Err(format_err!(
+                "Incomplete download of <{}>: received {} bytes out of {}",
+                url,
+                buf.len(),
+                content_length
+            ));
+        }
+        let data = match postprocess.map(|postprocess| postprocess(&buf)) {
+            Some(Ok(data)) => data,
+            Some(Err(e)) => {
+                pb.finish_and_clear();
+                return Err(e);
+            }
+            None => buf,
+        };
+        context
+            .udemy_helper
+            .write_file(part_filename.as_str(), &data)?;
+        context
+            .udemy_helper
+            .rename_file(part_filename.as_str(), target_filename)?;
+        let elapsed = elapsed_millis(start);
         pb.finish_with_message(
             format!(
                 "{:1.2} MB/s",
@@ -101,33 +190,62 @@ impl Download {
             )
             .as_str(),
         );
-        Ok(())
+        Ok(content_length)
     }
 
-    fn determine_quality(
+    /// Run a request on a signed media url. When the url expired, which udemy
+    /// answers with a 403, it is refreshed and the request run again.
+    fn with_fresh_url<T>(
         &self,
-        download_urls: &[DownloadUrl],
-        wanted_quality: Option<VideoQuality>,
+        url: &mut String,
+        refreshes: &mut u32,
+        refresh_url: &dyn Fn() -> Result<String>,
+        verbose: bool,
+        request: &mut dyn FnMut(&str) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            match request(url.as_str()) {
+                Err(ref e) if is_expired(e) && *refreshes < MAX_URL_REFRESHES => {
+                    if verbose {
+                        println!("\tDownload url expired, getting a new one");
+                    }
+                    *refreshes += 1;
+                    *url = refresh_url()?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get the download url of a quality again, out of a fresh lecture detail.
+    fn refresh_download_url(
+        &self,
+        context: &CommandContext,
+        lecture: &Lecture,
+        chosen: &DownloadUrl,
     ) -> Result<String> {
-        let quality = match wanted_quality {
-            Some(quality) => download_urls
-                .iter()
-                .filter(|url| url.r#type == Some("video/mp4".into()))
-                .map(|url| &url.label)
-                .filter_map(|label| label.parse::<VideoQuality>().ok())
-                .filter(|label| *label >= quality)
-                .min(),
-            None => download_urls
-                .iter()
-                .filter(|url| url.r#type == Some("video/mp4".into()))
-                .map(|url| &url.label)
-                .filter_map(|label| label.parse::<VideoQuality>().ok())
-                .max(),
-        };
-        let quality = quality
-            .map(|q| q.to_string())
-            .ok_or_else(|| format_err!("No best quality could be found"))?;
-        Ok(quality)
+        context
+            .get_lecture_detail(lecture)?
+            .asset
+            .download_urls
+            .unwrap_or_default()
+            .into_iter()
+            .find(|url| url.label == chosen.label && url.r#type == chosen.r#type)
+            .map(|url| url.file)
+            .ok_or_else(|| format_err!("No download url anymore for quality {}", chosen.label))
+    }
+
+    /// Choose the download url of a lecture following the quality policy, the
+    /// size of the candidates being probed for `maxsize` rules only.
+    fn determine_quality<'u>(
+        &self,
+        client: &dyn HttpClient,
+        download_urls: &'u [DownloadUrl],
+        wanted_quality: &QualityPolicy,
+    ) -> Result<(&'u DownloadUrl, QualityRule)> {
+        wanted_quality.select(download_urls, &mut |url| {
+            client.get_content_length(url.file.as_str())
+        })
     }
 
     fn download_chapter(
@@ -135,10 +253,18 @@ impl Download {
         context: &CommandContext,
         chapter: &Chapter,
         wanted_lecture: Option<LectureId>,
-        wanted_quality: Option<VideoQuality>,
+        wanted_ids: Option<&HashSet<LectureId>>,
+        wanted_quality: &QualityPolicy,
         output: &str,
         dry_run: bool,
         verbose: bool,
+        audio_only: bool,
+        tag: bool,
+        nfo: bool,
+        html: bool,
+        completed: Option<&HashSet<LectureId>>,
+        details: &mut HashMap<LectureId, LectureDetail>,
+        summary: &mut Summary,
     ) -> Result<()> {
         if verbose {
             println!(
@@ -146,140 +272,830 @@ impl Download {
                 chapter.object_index, chapter.title
             );
         }
-        let chapter_path = context
+        let chapter_path = context.udemy_helper.calculate_target_dir(
+            output,
+            &chapter,
+            context.course_name.as_str(),
+        )?;
+        context
             .udemy_helper
-            .calculate_target_dir(output, &chapter, context.course_name.as_str())
-            .unwrap();
-        if context
-            .udemy_helper
-            .create_target_dir(chapter_path.as_str())
-            .is_ok()
-        {
-            chapter
-                .lectures
-                .iter()
-                .filter(|lecture| {
-                    wanted_lecture.is_none() || wanted_lecture.unwrap() == lecture.object_index
-                })
-                .filter(|lecture| lecture.has_video)
-                .for_each(move |lecture| {
-                    match self.download_lecture(
-                        context,
-                        &lecture,
-                        wanted_quality,
-                        chapter_path.as_str(),
-                        dry_run,
-                        verbose,
-                    ) {
-                        Ok(_) => {
-                            // if verbose {
-                            //     println!("Lecture downloaded");
-                            // }
+            .create_target_dir(chapter_path.as_str())?;
+        if nfo && !dry_run {
+            let mut path_buf = PathBuf::from(chapter_path.as_str());
+            path_buf.push(SEASON_NFO);
+            context.udemy_helper.write_file(
+                path_buf.to_string_lossy().as_ref(),
+                season_nfo(chapter).as_bytes(),
+            )?;
+        }
+        chapter
+            .lectures
+            .iter()
+            .filter(|lecture| {
+                wanted_lecture.is_none() || wanted_lecture.unwrap() == lecture.object_index
+            })
+            .filter(|lecture| wanted_ids.is_none_or(|ids| ids.contains(&lecture.id)))
+            .take_while(|_| !context.is_interrupted())
+            .for_each(|lecture| {
+                if !is_downloadable(lecture, html) {
+                    summary.push(
+                        LectureOutcome::new(chapter, lecture, Status::Skipped)
+                            .with_reason("not a video nor a file"),
+                    );
+                    return;
+                }
+                let start = Instant::now();
+                let mut outcome = match self.download_lecture(
+                    context,
+                    chapter,
+                    &lecture,
+                    details.remove(&lecture.id),
+                    wanted_quality,
+                    chapter_path.as_str(),
+                    dry_run,
+                    verbose,
+                    audio_only,
+                    tag,
+                    nfo,
+                    html,
+                ) {
+                    Ok(outcome) => {
+                        if html && outcome.status == Status::Downloaded {
+                            self.save_resources(context, lecture, chapter_path.as_str(), verbose);
                         }
-                        Err(e) => {
-                            eprintln!("Error while saving {}: {}", lecture.title, e);
+                        match completed {
+                            Some(completed)
+                                if outcome.status == Status::Downloaded
+                                    && !completed.contains(&lecture.id) =>
+                            {
+                                self.mark_complete(context, lecture, outcome, verbose)
+                            }
+                            _ => outcome,
                         }
-                    };
-                });
-        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error while saving {}: {}", lecture.title, e);
+                        LectureOutcome::new(chapter, lecture, Status::Failed)
+                            .with_reason(e.to_string().as_str())
+                    }
+                };
+                outcome.duration_ms = elapsed_millis(start);
+                summary.push(outcome);
+            });
         Ok(())
     }
 
+    /// Mark a downloaded lecture as completed. The lecture stays downloaded when
+    /// this fails, the reason telling it could not be marked.
+    fn mark_complete(
+        &self,
+        context: &CommandContext,
+        lecture: &Lecture,
+        outcome: LectureOutcome,
+        verbose: bool,
+    ) -> LectureOutcome {
+        if verbose {
+            println!("\tMarking lecture {} as completed", lecture.title);
+        }
+        match context.mark_lecture_completed(lecture, true) {
+            Ok(_) => outcome,
+            Err(e) => {
+                eprintln!("Error while marking {} as completed: {}", lecture.title, e);
+                outcome.with_reason(format!("not marked as completed: {}", e).as_str())
+            }
+        }
+    }
+
     fn download_lecture(
         &self,
         context: &CommandContext,
+        chapter: &Chapter,
         lecture: &Lecture,
-        wanted_quality: Option<VideoQuality>,
+        lecture_detail: Option<LectureDetail>,
+        wanted_quality: &QualityPolicy,
         path: &str,
         dry_run: bool,
         verbose: bool,
-    ) -> Result<()> {
-        let target_filename = context
+        audio_only: bool,
+        tag: bool,
+        nfo: bool,
+        html: bool,
+    ) -> Result<LectureOutcome> {
+        let audio_only = audio_only && lecture.has_video;
+        let mut target_filename = context
             .udemy_helper
-            .calculate_target_filename(path, &lecture)
-            .unwrap();
-        let url = format!(
-            "https://{portal_name}.udemy.com/api-2.0/users/me/subscribed-courses/{course_id}/lectures/{lecture_id}?fields[asset]=@min,download_urls,external_url,slide_urls,status,captions,thumbnail_url,time_estimation,stream_urls&fields[caption]=@default,is_translation&fields[course]=id,url,locale&fields[lecture]=@default,course,can_give_cc_feedback,download_url",
-            portal_name = context.portal_name,
-            course_id = context.course.as_ref().unwrap().id, lecture_id=lecture.id
-        );
-
-        let lecture_detail = context.client.get_as_json(url.as_str(), &context.auth)?;
-        let lecture_detail = context.parser.parse_lecture_detail(&lecture_detail)?;
+            .calculate_target_filename(path, &lecture)?;
+        if audio_only {
+            target_filename = Path::new(target_filename.as_str())
+                .with_extension("m4a")
+                .to_string_lossy()
+                .into();
+        }
+        let lecture_detail = match lecture_detail {
+            Some(lecture_detail) => lecture_detail,
+            None => context.get_lecture_detail(lecture)?,
+        };
+        if html && lecture.asset_type == "Article" {
+            return self.save_article(
+                context,
+                chapter,
+                lecture,
+                &lecture_detail,
+                target_filename.as_str(),
+                dry_run,
+                verbose,
+            );
+        }
         if let Some(download_urls) = &lecture_detail.asset.download_urls {
-            let best_quality = self.determine_quality(&download_urls, wanted_quality)?;
-            for url in download_urls {
-                if let Some(video_type) = &url.r#type {
-                    if url.label == best_quality && video_type == "video/mp4" {
-                        if verbose {
-                            println!("\tGetting ({}) {}", url.label, url.file);
-                            println!("\t\t-> {}", target_filename);
-                        }
-                        if !dry_run {
-                            self.download_url(
-                                context,
-                                lecture.title.as_str(),
-                                url.file.as_str(),
-                                target_filename.as_str(),
-                            )?
-                        }
-                    }
+            let (url, rule) =
+                self.determine_quality(context.client, &download_urls, wanted_quality)?;
+            println!("\t{}: quality {} ({})", lecture.title, url.label, rule);
+            if verbose {
+                println!("\tGetting ({}) {}", url.label, url.file);
+                println!("\t\t-> {}", target_filename);
+            }
+            if dry_run {
+                return Ok(
+                    LectureOutcome::new(chapter, lecture, Status::Skipped).with_reason("dry run")
+                );
+            }
+            let thumbnail_url = lecture_detail.asset.thumbnail_url.as_deref();
+            let postprocess = |data: &[u8]| {
+                let tags = self.make_tags(context, chapter, lecture, thumbnail_url);
+                if audio_only {
+                    return extract_audio(data, &tags);
                 }
+                // An untagged video is still better than none.
+                write_tags(data, &tags).or_else(|e| {
+                    eprintln!("Could not tag {}: {}", lecture.title, e);
+                    Ok(data.to_vec())
+                })
+            };
+            let bytes = self.download_url(
+                context,
+                lecture.title.as_str(),
+                url.file.as_str(),
+                &|| self.refresh_download_url(context, lecture, url),
+                if audio_only || (tag && lecture.has_video) {
+                    Some(&postprocess)
+                } else {
+                    None
+                },
+                target_filename.as_str(),
+                verbose,
+            )?;
+            if nfo && lecture.has_video {
+                self.write_episode_metadata(
+                    context,
+                    chapter,
+                    lecture,
+                    &lecture_detail,
+                    target_filename.as_str(),
+                );
+            }
+            if html && lecture.has_video {
+                self.write_captions(context, lecture, &lecture_detail, target_filename.as_str());
+            }
+            return Ok(LectureOutcome::new(chapter, lecture, Status::Downloaded).with_bytes(bytes));
+        }
+        Ok(LectureOutcome::new(chapter, lecture, Status::Skipped)
+            .with_reason("no download available"))
+    }
+
+    /// Save the body of an article as a page next to the other lectures.
+    fn save_article(
+        &self,
+        context: &CommandContext,
+        chapter: &Chapter,
+        lecture: &Lecture,
+        lecture_detail: &LectureDetail,
+        target_filename: &str,
+        dry_run: bool,
+        verbose: bool,
+    ) -> Result<LectureOutcome> {
+        let body = match &lecture_detail.asset.body {
+            Some(body) => body,
+            None => {
+                return Ok(LectureOutcome::new(chapter, lecture, Status::Skipped)
+                    .with_reason("no article body"))
+            }
+        };
+        let article_filename = Path::new(target_filename).with_extension("html");
+        if verbose {
+            println!("\tSaving article {}", lecture.title);
+            println!("\t\t-> {}", article_filename.to_string_lossy());
+        }
+        if dry_run {
+            return Ok(
+                LectureOutcome::new(chapter, lecture, Status::Skipped).with_reason("dry run")
+            );
+        }
+        let page = article_page(lecture.title.as_str(), body.as_str());
+        context
+            .udemy_helper
+            .write_file(article_filename.to_string_lossy().as_ref(), page.as_bytes())?;
+        Ok(LectureOutcome::new(chapter, lecture, Status::Downloaded).with_bytes(page.len() as u64))
+    }
+
+    /// Save the files attached to a lecture next to it, for the viewer to link
+    /// them. Files already there are kept, and failures are only reported, the
+    /// lecture being there.
+    fn save_resources(
+        &self,
+        context: &CommandContext,
+        lecture: &Lecture,
+        path: &str,
+        verbose: bool,
+    ) {
+        let first_url = |asset: &SupplementaryAsset| {
+            context
+                .get_supplementary_asset_urls(lecture, asset)?
+                .into_iter()
+                .next()
+                .map(|url| url.file)
+                .ok_or_else(|| format_err!("No download available"))
+        };
+        for asset in lecture
+            .supplementary_assets
+            .iter()
+            .filter(|asset| asset.asset_type == "File")
+        {
+            let result = context
+                .udemy_helper
+                .calculate_asset_filename(path, lecture, asset)
+                .and_then(|asset_filename| {
+                    if context.udemy_helper.file_exists(asset_filename.as_str()) {
+                        return Ok(());
+                    }
+                    if verbose {
+                        println!("\tSaving resource {}", asset.title);
+                        println!("\t\t-> {}", asset_filename);
+                    }
+                    self.download_url(
+                        context,
+                        asset.title.as_str(),
+                        first_url(asset)?.as_str(),
+                        &|| first_url(asset),
+                        None,
+                        asset_filename.as_str(),
+                        verbose,
+                    )
+                    .map(|_| ())
+                });
+            if let Err(e) = result {
+                eprintln!(
+                    "Could not save the resource {} of {}: {}",
+                    asset.title, lecture.title, e
+                );
+            }
+        }
+    }
+
+    /// Save the captions of a video in its original language, next to it, as
+    /// `<video>.<locale>.vtt`. Failures are only reported, the video being there.
+    fn write_captions(
+        &self,
+        context: &CommandContext,
+        lecture: &Lecture,
+        lecture_detail: &LectureDetail,
+        target_filename: &str,
+    ) {
+        let target = Path::new(target_filename);
+        let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+        for caption in lecture_detail
+            .asset
+            .captions
+            .iter()
+            .filter(|caption| !caption.is_translation)
+        {
+            let caption_filename =
+                target.with_file_name(format!("{}.{}.vtt", stem, caption.locale));
+            let result = self
+                .get_file(context, caption.url.as_str())
+                .and_then(|data| {
+                    context
+                        .udemy_helper
+                        .write_file(caption_filename.to_string_lossy().as_ref(), &data)
+                });
+            if let Err(e) = result {
+                eprintln!(
+                    "Could not save the {} captions of {}: {}",
+                    caption.label, lecture.title, e
+                );
+            }
+        }
+    }
+
+    /// Tags of a lecture: the course as album, the chapter as grouping, the
+    /// instructors as artist and the thumbnail of the lecture as cover.
+    fn make_tags(
+        &self,
+        context: &CommandContext,
+        chapter: &Chapter,
+        lecture: &Lecture,
+        thumbnail_url: Option<&str>,
+    ) -> Mp4Tags {
+        let course = context.course.as_ref();
+        let album = course
+            .map(|course| course.title.clone())
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| context.course_name.clone());
+        let artist = course
+            .map(|course| {
+                course
+                    .visible_instructors
+                    .iter()
+                    .map(|instructor| instructor.title.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            })
+            .filter(|artist| !artist.is_empty());
+        let last = context
+            .course_content
+            .as_ref()
+            .and_then(|content| {
+                content
+                    .chapters
+                    .iter()
+                    .flat_map(|chapter| chapter.lectures.iter())
+                    .map(|lecture| lecture.object_index)
+                    .max()
+            })
+            .unwrap_or(lecture.object_index);
+        let cover = thumbnail_url.and_then(|url| match self.get_file(context, url) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                eprintln!("Could not get the thumbnail of {}: {}", lecture.title, e);
+                None
             }
+        });
+        Mp4Tags {
+            title: Some(lecture.title.clone()),
+            album: Some(album),
+            grouping: Some(chapter.title.clone()),
+            artist,
+            track: Some((lecture.object_index as u16, last as u16)),
+            cover,
+        }
+    }
+
+    fn get_file(&self, context: &CommandContext, url: &str) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        context.client.get_as_data(url, &mut buf, &mut |_| {})?;
+        Ok(buf)
+    }
+
+    /// Write the show metadata and the poster of the course, for media servers.
+    fn write_show_metadata(&self, context: &CommandContext, course_dir: &str) -> Result<()> {
+        let course = context
+            .course
+            .as_ref()
+            .ok_or_else(|| format_err!("Course should be loaded"))?;
+        let mut path_buf = PathBuf::from(course_dir);
+        path_buf.push(TVSHOW_NFO);
+        context.udemy_helper.write_file(
+            path_buf.to_string_lossy().as_ref(),
+            tvshow_nfo(course, context.course_name.as_str()).as_bytes(),
+        )?;
+        if let Some(image_url) = &course.image_url {
+            path_buf.set_file_name(POSTER);
+            let image = self.get_file(context, image_url.as_str())?;
+            context
+                .udemy_helper
+                .write_file(path_buf.to_string_lossy().as_ref(), &image)?;
         }
         Ok(())
     }
 
+    /// Write the episode metadata and the thumbnail next to a video. Failures
+    /// are only reported, the video being there.
+    fn write_episode_metadata(
+        &self,
+        context: &CommandContext,
+        chapter: &Chapter,
+        lecture: &Lecture,
+        lecture_detail: &LectureDetail,
+        target_filename: &str,
+    ) {
+        let target = Path::new(target_filename);
+        let nfo_filename = target.with_extension("nfo");
+        let result = context
+            .udemy_helper
+            .write_file(
+                nfo_filename.to_string_lossy().as_ref(),
+                episode_nfo(chapter, lecture, lecture_detail.description.as_str()).as_bytes(),
+            )
+            .and_then(|_| match &lecture_detail.asset.thumbnail_url {
+                Some(thumbnail_url) => {
+                    let thumb_filename = target.with_file_name(format!(
+                        "{}-thumb.jpg",
+                        target.file_stem().unwrap_or_default().to_string_lossy()
+                    ));
+                    let image = self.get_file(context, thumbnail_url.as_str())?;
+                    context
+                        .udemy_helper
+                        .write_file(thumb_filename.to_string_lossy().as_ref(), &image)
+                }
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            eprintln!("Could not write the metadata of {}: {}", lecture.title, e);
+        }
+    }
+
     pub fn download(
         &self,
         context: &CommandContext,
         wanted_chapter: Option<ObjectIndex>,
         wanted_lecture: Option<LectureId>,
-        wanted_quality: Option<VideoQuality>,
+        wanted_quality: &QualityPolicy,
         output: &str,
         dry_run: bool,
         verbose: bool,
-    ) -> Result<()> {
+        retry_failed: Option<&str>,
+        mark_complete: bool,
+        force: bool,
+        max_size: Option<u64>,
+        audio_only: bool,
+        tag: bool,
+        nfo: bool,
+        html: bool,
+    ) -> Result<Summary> {
         if verbose {
             println!(
-                "Download request chapter: {:?}, lecture: {:?}, quality: {:?}, dry_run: {}",
-                wanted_chapter, wanted_lecture, wanted_quality, dry_run
+                "Download request chapter: {:?}, lecture: {:?}, quality: {:?}, dry_run: {}, retry_failed: {:?}, mark_complete: {}",
+                wanted_chapter, wanted_lecture, wanted_quality, dry_run, retry_failed, mark_complete
+            );
+        }
+
+        let completed = if mark_complete && !dry_run {
+            Some(context.get_completed_lecture_ids()?)
+        } else {
+            None
+        };
+
+        let previous_failures = match retry_failed {
+            Some(retry_failed) => Some(self.load_failures(context, retry_failed)?),
+            None => None,
+        };
+        let wanted_ids: Option<HashSet<LectureId>> = previous_failures.as_ref().map(|failures| {
+            failures
+                .iter()
+                .filter(|failure| failure.course == context.course_name)
+                .map(|failure| failure.lecture_id)
+                .collect()
+        });
+
+        // Lecture details fetched for the estimate, used again for downloading.
+        let mut details = HashMap::new();
+        if !dry_run {
+            let (needed, unknown) = self.estimate_size(
+                context,
+                wanted_chapter,
+                wanted_lecture,
+                wanted_ids.as_ref(),
+                wanted_quality,
+                &mut details,
+            );
+            println!(
+                "Estimated download size: {}{}",
+                HumanBytes(needed),
+                if unknown > 0 {
+                    format!(" ({} videos of unknown size)", unknown)
+                } else {
+                    String::new()
+                }
             );
+            self.check_space(context, output, needed, force, max_size)?;
         }
 
+        // Held until the failures are saved, released when going out of scope.
+        let _lock = if dry_run {
+            None
+        } else {
+            let course_dir = context
+                .udemy_helper
+                .calculate_course_dir(output, context.course_name.as_str())?;
+            context
+                .udemy_helper
+                .create_target_dir(course_dir.as_str())?;
+            let lock = CourseLock::acquire(context.udemy_helper, course_dir.as_str())?;
+            if nfo {
+                if let Err(e) = self.write_show_metadata(context, course_dir.as_str()) {
+                    eprintln!("Could not write the metadata of the course: {}", e);
+                }
+            }
+            Some(lock)
+        };
+
+        let mut summary = Summary::new();
         for chapter in context.course_content.as_ref().unwrap().chapters.iter() {
-            if wanted_chapter.is_none() || wanted_chapter.unwrap() == chapter.object_index {
+            if context.is_interrupted() {
+                break;
+            }
+            let retried = wanted_ids.as_ref().is_none_or(|ids| {
+                chapter
+                    .lectures
+                    .iter()
+                    .any(|lecture| ids.contains(&lecture.id))
+            });
+            if (wanted_chapter.is_none() || wanted_chapter.unwrap() == chapter.object_index)
+                && retried
+            {
                 self.download_chapter(
                     context,
                     &chapter,
                     wanted_lecture,
+                    wanted_ids.as_ref(),
                     wanted_quality,
                     output,
                     dry_run,
                     verbose,
+                    audio_only,
+                    tag,
+                    nfo,
+                    html,
+                    completed.as_ref(),
+                    &mut details,
+                    &mut summary,
+                )?;
+            }
+        }
+
+        if !dry_run {
+            if let Err(e) = self.write_playlists(context, output) {
+                eprintln!("Could not write the playlists: {}", e);
+            }
+            if html {
+                if let Err(e) = self.write_viewer(context, output) {
+                    eprintln!("Could not write the viewer: {}", e);
+                }
+            }
+            self.save_failures(
+                context,
+                output,
+                retry_failed,
+                previous_failures.unwrap_or_default(),
+                &summary,
+            )?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Write a playlist of the videos found in each chapter directory, and one
+    /// of the whole course, in curriculum order. Videos downloaded as audio only
+    /// are taken as well.
+    fn write_playlists(&self, context: &CommandContext, output: &str) -> Result<()> {
+        let udemy_helper = context.udemy_helper;
+        let course_dir = udemy_helper.calculate_course_dir(output, context.course_name.as_str())?;
+        let mut course_entries = Vec::new();
+        for chapter in context.course_content.as_ref().unwrap().chapters.iter() {
+            let chapter_path =
+                udemy_helper.calculate_target_dir(output, chapter, context.course_name.as_str())?;
+            let chapter_dir = file_name(chapter_path.as_str());
+            let mut chapter_entries = Vec::new();
+            for lecture in chapter.lectures.iter().filter(|lecture| lecture.has_video) {
+                let video =
+                    udemy_helper.calculate_target_filename(chapter_path.as_str(), lecture)?;
+                let audio = Path::new(video.as_str())
+                    .with_extension("m4a")
+                    .to_string_lossy()
+                    .into_owned();
+                if let Some(media) = [video, audio]
+                    .iter()
+                    .find(|media| udemy_helper.file_exists(media.as_str()))
+                {
+                    let media = file_name(media.as_str());
+                    chapter_entries.push(PlaylistEntry {
+                        duration: lecture.duration,
+                        title: lecture.title.clone(),
+                        path: media.clone(),
+                    });
+                    course_entries.push(PlaylistEntry {
+                        duration: lecture.duration,
+                        title: lecture.title.clone(),
+                        path: relative_path(&[chapter_dir.as_str(), media.as_str()]),
+                    });
+                }
+            }
+            if !chapter_entries.is_empty() {
+                let mut path_buf = PathBuf::from(chapter_path.as_str());
+                path_buf.push(format!("{}.{}", chapter_dir, PLAYLIST_EXTENSION));
+                udemy_helper.write_file(
+                    path_buf.to_string_lossy().as_ref(),
+                    render_m3u8(&chapter_entries).as_bytes(),
                 )?;
             }
         }
+        if !course_entries.is_empty() {
+            let mut path_buf = PathBuf::from(course_dir.as_str());
+            path_buf.push(format!("{}.{}", context.course_name, PLAYLIST_EXTENSION));
+            udemy_helper.write_file(
+                path_buf.to_string_lossy().as_ref(),
+                render_m3u8(&course_entries).as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the offline viewer of the course, out of the files found in each
+    /// chapter directory.
+    fn write_viewer(&self, context: &CommandContext, output: &str) -> Result<()> {
+        let udemy_helper = context.udemy_helper;
+        let course_dir = udemy_helper.calculate_course_dir(output, context.course_name.as_str())?;
+        let mut chapters = Vec::new();
+        for chapter in context.course_content.as_ref().unwrap().chapters.iter() {
+            let chapter_path =
+                udemy_helper.calculate_target_dir(output, chapter, context.course_name.as_str())?;
+            // A chapter not downloaded yet has no directory.
+            let files = udemy_helper
+                .list_dir(chapter_path.as_str())
+                .unwrap_or_default();
+            chapters.push(viewer_chapter(
+                udemy_helper,
+                chapter,
+                chapter_path.as_str(),
+                &files,
+            )?);
+        }
+        let title = context
+            .course
+            .as_ref()
+            .map(|course| course.title.as_str())
+            .filter(|title| !title.is_empty())
+            .unwrap_or(context.course_name.as_str());
+        let mut path_buf = PathBuf::from(course_dir);
+        path_buf.push(INDEX_FILENAME);
+        udemy_helper.write_file(
+            path_buf.to_string_lossy().as_ref(),
+            render_index(title, context.course_name.as_str(), &chapters)?.as_bytes(),
+        )
+    }
+
+    /// Estimate the bytes to download for a selection, out of the size of the
+    /// chosen quality of each video, telling how many videos could not be probed.
+    /// Videos already saved count as well, being downloaded again next to them.
+    /// The lecture details fetched are kept in `details`.
+    fn estimate_size(
+        &self,
+        context: &CommandContext,
+        wanted_chapter: Option<ObjectIndex>,
+        wanted_lecture: Option<LectureId>,
+        wanted_ids: Option<&HashSet<LectureId>>,
+        wanted_quality: &QualityPolicy,
+        details: &mut HashMap<LectureId, LectureDetail>,
+    ) -> (u64, usize) {
+        let mut needed = 0;
+        let mut unknown = 0;
+        let lectures = context
+            .course_content
+            .as_ref()
+            .unwrap()
+            .chapters
+            .iter()
+            .filter(|chapter| wanted_chapter.is_none_or(|wanted| wanted == chapter.object_index))
+            .flat_map(|chapter| chapter.lectures.iter())
+            .filter(|lecture| wanted_lecture.is_none_or(|wanted| wanted == lecture.object_index))
+            .filter(|lecture| wanted_ids.is_none_or(|ids| ids.contains(&lecture.id)))
+            .filter(|lecture| is_downloadable(lecture, false));
+        for lecture in lectures {
+            let size = context.get_lecture_detail(lecture).and_then(|detail| {
+                let size = match &detail.asset.download_urls {
+                    Some(download_urls) => {
+                        let (url, _) =
+                            self.determine_quality(context.client, download_urls, wanted_quality)?;
+                        context.client.get_content_length(url.file.as_str())
+                    }
+                    None => Ok(0),
+                };
+                details.insert(lecture.id, detail);
+                size
+            });
+            match size {
+                Ok(size) => needed += size,
+                Err(_) => unknown += 1,
+            }
+        }
+        (needed, unknown)
+    }
 
+    /// Refuse to download more than the size budget or than the free space on
+    /// the output filesystem, the latter being only a warning when forced.
+    fn check_space(
+        &self,
+        context: &CommandContext,
+        output: &str,
+        needed: u64,
+        force: bool,
+        max_size: Option<u64>,
+    ) -> Result<()> {
+        if let Some(max_size) = max_size {
+            if needed > max_size {
+                return Err(format_err!(
+                    "Estimated download size {} exceeds the maximum size of {}",
+                    HumanBytes(needed),
+                    HumanBytes(max_size)
+                ));
+            }
+        }
+        let available = context.udemy_helper.available_space(output)?;
+        if needed > available {
+            let error = UdemyError::InsufficientSpace {
+                path: output.into(),
+                needed,
+                available,
+            };
+            if !force {
+                return Err(error.into());
+            }
+            eprintln!("{}, downloading anyway", error);
+        }
         Ok(())
     }
+
+    /// Load the failures of a previous run. Those of other courses are only
+    /// kept, to be written back.
+    fn load_failures(&self, context: &CommandContext, path: &str) -> Result<Vec<Failure>> {
+        let failures = parse_failures(context.udemy_helper.read_file(path)?.as_str())?;
+        let others = failures
+            .iter()
+            .filter(|failure| failure.course != context.course_name)
+            .count();
+        if others > 0 {
+            eprintln!(
+                "Leaving {} failures of other courses in {} as they are",
+                others, path
+            );
+        }
+        Ok(failures)
+    }
+
+    /// Write the lectures that failed to the failures file, keeping previous failures
+    /// that were not attempted again during this run, and those of other courses.
+    fn save_failures(
+        &self,
+        context: &CommandContext,
+        output: &str,
+        retry_failed: Option<&str>,
+        previous_failures: Vec<Failure>,
+        summary: &Summary,
+    ) -> Result<()> {
+        let failures = merge_failures(context.course_name.as_str(), previous_failures, summary);
+        let path = match retry_failed {
+            Some(retry_failed) => String::from(retry_failed),
+            None => {
+                let course_dir = context
+                    .udemy_helper
+                    .calculate_course_dir(output, context.course_name.as_str())?;
+                context
+                    .udemy_helper
+                    .create_target_dir(course_dir.as_str())?;
+                let mut path_buf = PathBuf::from(course_dir);
+                path_buf.push(FAILURES_FILENAME);
+                path_buf.to_string_lossy().into()
+            }
+        };
+        context
+            .udemy_helper
+            .write_file(path.as_str(), failures_to_json(&failures)?.as_bytes())
+    }
+}
+
+/// Tell whether a lecture has a media to download, a video or a plain file,
+/// and an article when writing the viewer.
+fn is_downloadable(lecture: &Lecture, html: bool) -> bool {
+    lecture.has_video || lecture.asset_type == "File" || (html && lecture.asset_type == "Article")
+}
+
+/// Tell whether an error comes from an expired signed url.
+fn is_expired(error: &failure::Error) -> bool {
+    match error.downcast_ref::<UdemyError>() {
+        Some(UdemyError::HttpStatus { status, .. }) => *status == StatusCode::FORBIDDEN,
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use std::sync::atomic::AtomicBool;
+
     use crate::mocks::test::*;
     use crate::test_data::test::*;
     use crate::udemy_helper::UdemyHelper;
 
     #[test]
     fn download() {
+        let _mocks = lock_mocks();
         unsafe {
             PARSE = Some(vec![]);
             GETS_AS_JSON_URL = Some(vec![]);
             GETS_CONTENT_LENGTH_URL = Some(vec![]);
             GETS_AS_DATA_URL = Some(vec![]);
+            WRITE_FILE_PATH = Some(vec![]);
+            RENAME_FILE_PATH = Some(vec![]);
         }
 
         let fs_helper = MockFsHelper {};
@@ -309,62 +1125,603 @@ mod test {
             output: "~/Downloads".into(),
             dry_run: false,
             verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
         });
 
         let result = download.execute(&context);
 
         assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.total(), 1);
+        assert_eq!(summary.count(Status::Downloaded), 1);
+        assert_eq!(summary.outcomes[0].lecture_id, 4321);
         unsafe {
             if let Some(ref gcl) = GETS_CONTENT_LENGTH_URL {
-                assert_eq!(gcl.len(), 1);
-                assert_eq!(gcl[0], "http://host-name/the-filename.mp4");
+                // Probed once for the size estimate, once for the download.
+                assert_eq!(gcl.len(), 2);
+                assert_eq!(gcl[1], "http://host-name/the-filename.mp4");
             }
             if let Some(ref gad) = GETS_AS_DATA_URL {
                 assert_eq!(gad.len(), 1);
                 assert_eq!(gad[0], "http://host-name/the-filename.mp4");
             }
+            if let Some(ref wfp) = WRITE_FILE_PATH {
+                assert!(wfp[0].ends_with(".mp4.part"));
+                assert!(wfp.contains(
+                    &"~/Downloads/css-the-complete-guide-incl-flexbox-grid-sass/001 The Chapter/001 The Chapter.m3u8".to_string()
+                ));
+                assert!(wfp.contains(
+                    &"~/Downloads/css-the-complete-guide-incl-flexbox-grid-sass/css-the-complete-guide-incl-flexbox-grid-sass.m3u8".to_string()
+                ));
+            }
+            if let Some(ref rfp) = RENAME_FILE_PATH {
+                assert_eq!(rfp.len(), 1);
+                assert!(rfp[0].ends_with(".mp4"));
+            }
         }
     }
 
     #[test]
-    fn determine_quality_for_best() {
-        let download_urls = vec![
-            DownloadUrl {
-                label: "480".into(),
-                file: "the-file-video-480".into(),
-                r#type: Some("video/mp4".into()),
-            },
-            DownloadUrl {
-                label: "720".into(),
-                file: "the-file-video-720".into(),
-                r#type: Some("video/mp4".into()),
-            },
-            DownloadUrl {
-                label: "1720".into(),
-                file: "the-file-720".into(),
-                r#type: Some("other/mp4".into()),
-            },
-        ];
-        let wanted_quality = None;
-
-        let mut download = Download::new();
-        download.set_params(&DownloadParams {
-            wanted_chapter: Some(1),
-            wanted_lecture: Some(1),
+    fn download_with_html() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+            WRITE_FILE_PATH = Some(vec![]);
+            RENAME_FILE_PATH = Some(vec![]);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        let mut course_content = make_test_course_content();
+        course_content.chapters[0].lectures[0]
+            .supplementary_assets
+            .push(SupplementaryAsset {
+                id: 1,
+                title: "exercise.zip".into(),
+                asset_type: "File".into(),
+            });
+        context.course_content = Some(course_content);
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: Some(1),
+            wanted_lecture: Some(1),
             wanted_quality: None,
             output: "~/Downloads".into(),
             dry_run: false,
             verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: true,
         });
 
-        let actual = download.determine_quality(&download_urls, wanted_quality);
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        unsafe {
+            if let Some(ref gad) = GETS_AS_DATA_URL {
+                assert!(gad.contains(&"http://host-name/the-caption.vtt".to_string()));
+                assert!(gad.contains(&"http://host-name/exercise.zip".to_string()));
+            }
+            if let Some(ref wfp) = WRITE_FILE_PATH {
+                assert!(wfp.iter().any(|path| path.ends_with(".en_US.vtt")));
+                assert!(wfp.contains(
+                    &"~/Downloads/css-the-complete-guide-incl-flexbox-grid-sass/index.html"
+                        .to_string()
+                ));
+            }
+            if let Some(ref rfp) = RENAME_FILE_PATH {
+                // Named as the viewer looks for it.
+                assert!(rfp
+                    .iter()
+                    .any(|rename| rename.ends_with("/001 The Chapter/001 exercise.zip")));
+            }
+        }
+    }
+
+    #[test]
+    fn download_checks_space() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+            AVAILABLE_SPACE = Some(100);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let download = Download::new();
+        let run = |force, max_size| {
+            download.download(
+                &context,
+                None,
+                None,
+                &QualityPolicy::default(),
+                "~/Downloads",
+                false,
+                false,
+                None,
+                false,
+                force,
+                max_size,
+                false,
+                false,
+                false,
+                false,
+            )
+        };
+
+        // The video already saved needs room for its .part file as well.
+        let not_enough_space = run(false, None);
+        let forced = run(true, None);
+        let over_budget = run(true, Some(300));
+        unsafe {
+            AVAILABLE_SPACE = None;
+        }
+
+        match not_enough_space.unwrap_err().downcast_ref::<UdemyError>() {
+            Some(UdemyError::InsufficientSpace {
+                needed, available, ..
+            }) => {
+                assert_eq!(*needed, 321);
+                assert_eq!(*available, 100);
+            }
+            _ => panic!("expected an insufficient space error"),
+        }
+        assert_eq!(forced.unwrap().count(Status::Downloaded), 1);
+        assert!(over_budget.is_err());
+        unsafe {
+            if let Some(ref gad) = GETS_AS_DATA_URL {
+                assert_eq!(gad.len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn download_interrupted() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+        }
+
+        let fs_helper = MockFsHelper {};
+        let interrupted = AtomicBool::new(true);
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap()
+        .with_interrupted(&interrupted);
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: None,
+            wanted_lecture: None,
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_empty());
+        unsafe {
+            if let Some(ref gad) = GETS_AS_DATA_URL {
+                assert!(gad.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn download_refreshes_expired_url() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+            GETS_AS_DATA_EXPIRED = Some(vec!["http://host-name/the-filename.mp4".into()]);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: None,
+            wanted_lecture: None,
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.count(Status::Downloaded), 1);
+        assert_eq!(summary.outcomes[0].bytes, 321);
+        unsafe {
+            if let Some(ref gaj) = GETS_AS_JSON_URL {
+                assert_eq!(gaj.len(), 2);
+                assert_eq!(gaj[0], gaj[1]);
+            }
+            if let Some(ref gad) = GETS_AS_DATA_URL {
+                assert_eq!(gad.len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn download_mark_complete() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+            POST_JSON_DATA_URL = Some(vec![]);
+            POST_JSON_DATA_BODY = Some(vec![]);
+            COMPLETED_LECTURE_IDS = None;
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: None,
+            wanted_lecture: None,
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: None,
+            mark_complete: true,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.count(Status::Downloaded), 1);
+        assert_eq!(summary.outcomes[0].bytes, 321);
+        unsafe {
+            if let Some(ref pjd) = POST_JSON_DATA_URL {
+                assert_eq!(pjd.len(), 1);
+                assert_eq!(pjd[0], "https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/completed-lectures/");
+            }
+            if let Some(ref pjdb) = POST_JSON_DATA_BODY {
+                assert_eq!(pjdb.len(), 1);
+                assert_eq!(pjdb[0], "{\"downloaded\":true,\"lecture_id\":4321}");
+            }
+        }
+    }
+
+    #[test]
+    fn download_retry_failed() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: None,
+            wanted_lecture: None,
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: Some("~/Downloads/failures.json".into()),
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        let summary = result.unwrap();
+        assert_eq!(summary.total(), 1);
+        assert_eq!(summary.outcomes[0].lecture_id, 4321);
+        assert_eq!(summary.count(Status::Downloaded), 1);
+    }
+
+    #[test]
+    fn download_retry_nothing_failed() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+            GETS_AS_DATA_URL = Some(vec![]);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: None,
+            wanted_lecture: None,
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: Some("~/Downloads/empty.json".into()),
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let result = download.execute(&context);
+
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[test]
+    fn make_tags_from_course() {
+        let _mocks = lock_mocks();
+        unsafe {
+            GETS_AS_DATA_URL = Some(vec![]);
+        }
+        let fs_helper = MockFsHelper {};
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            Auth::with_token("blah"),
+        )
+        .unwrap();
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+        let course_content = make_test_course_content();
+        let chapter = &course_content.chapters[0];
+
+        let tags = Download::new().make_tags(
+            &context,
+            chapter,
+            &chapter.lectures[0],
+            Some("http://host-name/the-thumbnail.jpg"),
+        );
+
+        assert_eq!(tags.title, Some(chapter.lectures[0].title.clone()));
+        assert_eq!(tags.album, Some("CSS - The Complete Guide".into()));
+        assert_eq!(tags.grouping, Some("The Chapter".into()));
+        assert_eq!(tags.artist, Some("Maximilian Schwarzmüller".into()));
+        assert_eq!(tags.track, Some((1, 1)));
+        assert_eq!(tags.cover.map(|cover| cover.len()), Some(321));
+        unsafe {
+            if let Some(ref gad) = GETS_AS_DATA_URL {
+                assert_eq!(gad.len(), 1);
+                assert_eq!(gad[0], "http://host-name/the-thumbnail.jpg");
+            }
+        }
+    }
+
+    #[test]
+    fn determine_quality_for_best() {
+        let _mocks = lock_mocks();
+        let download_urls = vec![
+            DownloadUrl {
+                label: "480".into(),
+                file: "the-file-video-480".into(),
+                r#type: Some("video/mp4".into()),
+            },
+            DownloadUrl {
+                label: "720".into(),
+                file: "the-file-video-720".into(),
+                r#type: Some("video/mp4".into()),
+            },
+            DownloadUrl {
+                label: "1720".into(),
+                file: "the-file-720".into(),
+                r#type: Some("other/mp4".into()),
+            },
+        ];
+        let wanted_quality = QualityPolicy::default();
+
+        let mut download = Download::new();
+        download.set_params(&DownloadParams {
+            wanted_chapter: Some(1),
+            wanted_lecture: Some(1),
+            wanted_quality: None,
+            output: "~/Downloads".into(),
+            dry_run: false,
+            verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
+        });
+
+        let actual =
+            download.determine_quality(&MockHttpClient {}, &download_urls, &wanted_quality);
 
         assert_eq!(actual.is_ok(), true);
-        assert_eq!(actual.unwrap(), "720");
+        assert_eq!(actual.unwrap().0.label, "720");
     }
 
     #[test]
     fn determine_quality_for_wanted_480() {
+        let _mocks = lock_mocks();
         let download_urls = vec![
             DownloadUrl {
                 label: "480".into(),
@@ -387,7 +1744,7 @@ mod test {
                 r#type: Some("other/mp4".into()),
             },
         ];
-        let wanted_quality = Some(480u64);
+        let wanted_quality = "480".parse::<QualityPolicy>().unwrap();
         let mut download = Download::new();
         download.set_params(&DownloadParams {
             wanted_chapter: Some(1),
@@ -396,16 +1753,26 @@ mod test {
             output: "~/Downloads".into(),
             dry_run: false,
             verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
         });
 
-        let actual = download.determine_quality(&download_urls, wanted_quality);
+        let actual =
+            download.determine_quality(&MockHttpClient {}, &download_urls, &wanted_quality);
 
         assert_eq!(actual.is_ok(), true);
-        assert_eq!(actual.unwrap(), "480");
+        assert_eq!(actual.unwrap().0.label, "480");
     }
 
     #[test]
     fn determine_quality_for_wanted_470() {
+        let _mocks = lock_mocks();
         let download_urls = vec![
             DownloadUrl {
                 label: "480".into(),
@@ -428,7 +1795,7 @@ mod test {
                 r#type: Some("other/mp4".into()),
             },
         ];
-        let wanted_quality = Some(470u64);
+        let wanted_quality = "470".parse::<QualityPolicy>().unwrap();
         let mut download = Download::new();
         download.set_params(&DownloadParams {
             wanted_chapter: Some(1),
@@ -437,11 +1804,37 @@ mod test {
             output: "~/Downloads".into(),
             dry_run: false,
             verbose: false,
+            retry_failed: None,
+            mark_complete: false,
+            force: false,
+            max_size: None,
+            audio_only: false,
+            tag: false,
+            nfo: false,
+            html: false,
         });
 
-        let actual = download.determine_quality(&download_urls, wanted_quality);
+        let actual =
+            download.determine_quality(&MockHttpClient {}, &download_urls, &wanted_quality);
 
         assert_eq!(actual.is_ok(), true);
-        assert_eq!(actual.unwrap(), "480");
+        assert_eq!(actual.unwrap().0.label, "480");
+    }
+
+    #[test]
+    fn determine_quality_for_wanted_720_on_file() {
+        let _mocks = lock_mocks();
+        let download_urls = vec![DownloadUrl {
+            label: "download".into(),
+            file: "http://host-name/exercise.zip".into(),
+            r#type: None,
+        }];
+        let wanted_quality = "720".parse::<QualityPolicy>().unwrap();
+        let download = Download::new();
+
+        let actual =
+            download.determine_quality(&MockHttpClient {}, &download_urls, &wanted_quality);
+
+        assert_eq!(actual.unwrap().0.file, "http://host-name/exercise.zip");
     }
 }
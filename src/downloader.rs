@@ -1,8 +1,11 @@
-use failure::format_err;
+use failure::Error;
+use reqwest::StatusCode;
 
 use crate::command::*;
+use crate::error::UdemyError;
 use crate::model::*;
 use crate::result::Result;
+use crate::summary::Summary;
 
 pub struct UdemyDownloader<'a> {
     command_context: &'a mut CommandContext<'a>,
@@ -17,13 +20,12 @@ impl<'a> UdemyDownloader<'a> {
 
     fn get_subscribed_course(&self, verbose: bool) -> Result<Course> {
         if verbose {
-            println!("Requesting subscribed courses");
+            eprintln!("Requesting subscribed courses");
         }
-        let url = format!(
-            "https://{portal_name}.udemy.com/api-2.0/users/me/subscribed-courses?fields[course]=id,url,published_title&page=1&page_size=1000&ordering=-access_time&search={course_name}",
-            portal_name = self.command_context.portal_name,
-            course_name = self.command_context.course_name
-        );
+        let url = self
+            .command_context
+            .endpoints
+            .subscribed_courses(self.command_context.course_name.as_str());
         let value = self
             .command_context
             .client
@@ -34,19 +36,18 @@ impl<'a> UdemyDownloader<'a> {
             .into_iter()
             .find(|course| course.published_title == self.command_context.course_name)
             .ok_or_else(|| {
-                format_err!(
-                    "{} was not found in subscribed courses",
-                    self.command_context.course_name
-                )
+                UdemyError::NotSubscribed {
+                    course_name: self.command_context.course_name.clone(),
+                }
+                .into()
             })
     }
 
     fn get_info(&self, course: &Course, verbose: bool) -> Result<String> {
-        let url = format!("https://{portal_name}.udemy.com/api-2.0/courses/{course_id}/subscriber-curriculum-items/?page_size=1400&fields[lecture]=@min,object_index,asset,supplementary_assets,sort_order,is_published,is_free&fields[quiz]=@min,object_index,title,sort_order,is_published&fields[practice]=@min,object_index,title,sort_order,is_published&fields[chapter]=@min,description,object_index,title,sort_order,is_published&fields[asset]=@min,title,filename,asset_type,external_url,length,status",
-        portal_name = self.command_context.portal_name, course_id=course.id);
+        let url = self.command_context.endpoints.curriculum(course.id);
 
         if verbose {
-            println!("Requesting info for course");
+            eprintln!("Requesting info for course");
         }
         self.command_context
             .client
@@ -54,17 +55,44 @@ impl<'a> UdemyDownloader<'a> {
     }
 
     fn parse_info(&self, info: &str) -> Result<CourseContent> {
-        let value = serde_json::from_str(info)?;
+        let value = serde_json::from_str(info).map_err(|e| UdemyError::Parse {
+            path: "$".into(),
+            message: e.to_string(),
+        })?;
         let course_content = self.command_context.parser.parse_course_content(&value)?;
         Ok(course_content)
     }
 
-    pub fn authenticate(&mut self) -> Result<()> {
-        // Previously, authentication with login form was supported
+    fn get_user(&self, verbose: bool) -> Result<User> {
+        if verbose {
+            eprintln!("Validating access token");
+        }
+        let url = self.command_context.endpoints.user();
+        let value = self
+            .command_context
+            .client
+            .get_as_json(url.as_str(), &self.command_context.auth)?;
+        self.command_context.parser.parse_user(&value)
+    }
+
+    /// Validate the access token by querying the authenticated user. Previously,
+    /// authentication with login form was supported. The account is told when
+    /// `announce`.
+    pub fn authenticate(&mut self, verbose: bool, announce: bool) -> Result<()> {
+        let user = self
+            .get_user(verbose)
+            .map_err(|e| to_authentication_error(e, &self.command_context.portal_name))?;
+        if announce {
+            eprintln!(
+                "Authenticated as {} on portal {}",
+                user.title, self.command_context.portal_name
+            );
+        }
+        self.command_context.user = Some(user);
         Ok(())
     }
 
-    pub fn execute(&self, command: &dyn Command) -> Result<()> {
+    pub fn execute(&self, command: &dyn Command) -> Result<Summary> {
         command.execute(&self.command_context)
     }
 
@@ -80,16 +108,41 @@ impl<'a> UdemyDownloader<'a> {
     }
 }
 
+/// Turn http errors received while validating the token into authentication errors.
+fn to_authentication_error(error: Error, portal_name: &str) -> Error {
+    let status = match error.downcast_ref::<UdemyError>() {
+        Some(UdemyError::HttpStatus { status, .. }) => *status,
+        _ => return error,
+    };
+    match status {
+        StatusCode::UNAUTHORIZED => UdemyError::Authentication {
+            message: format!(
+                "Access token expired or invalid for portal {} (401 Unauthorized), please provide a fresh token",
+                portal_name
+            ),
+        }
+        .into(),
+        StatusCode::FORBIDDEN => UdemyError::Authentication {
+            message: format!(
+                "Access token expired or invalid, or no access to portal {} (403 Forbidden), please provide a fresh token for this portal",
+                portal_name
+            ),
+        }
+        .into(),
+        _ => error,
+    }
+}
+
 #[cfg(test)]
 mod test_udemy_downloader {
 
-    use crate::command::*;
+    use super::*;
     use crate::mocks::test::*;
-    use crate::model::*;
     use crate::udemy_helper::UdemyHelper;
 
     #[test]
     fn parse_url() {
+        let _mocks = lock_mocks();
         unsafe {
             PARSE = Some(vec![]);
             GETS_AS_JSON_URL = Some(vec![]);
@@ -118,4 +171,99 @@ mod test_udemy_downloader {
         );
         assert_eq!(context.portal_name, "www");
     }
+
+    #[test]
+    fn authenticate() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+        }
+
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://acme.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+        let mut downloader = UdemyDownloader::new(&mut context);
+
+        let result = downloader.authenticate(false, false);
+
+        assert!(result.is_ok());
+        unsafe {
+            if let Some(ref gaj) = GETS_AS_JSON_URL {
+                assert_eq!(gaj.len(), 1);
+                assert_eq!(
+                    gaj[0],
+                    "https://acme.udemy.com/api-2.0/users/me/?fields[user]=id,title"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn authentication_error_for_unauthorized() {
+        let error = UdemyError::HttpStatus {
+            url: "the-url".into(),
+            status: StatusCode::UNAUTHORIZED,
+            detail: None,
+        };
+
+        let actual = to_authentication_error(error.into(), "www");
+
+        match actual.downcast_ref::<UdemyError>() {
+            Some(UdemyError::Authentication { message }) => {
+                assert!(message.contains("expired or invalid"));
+                assert!(message.contains("401"));
+            }
+            _ => panic!("expected an authentication error"),
+        }
+    }
+
+    #[test]
+    fn authentication_error_for_forbidden() {
+        let error = UdemyError::HttpStatus {
+            url: "the-url".into(),
+            status: StatusCode::FORBIDDEN,
+            detail: None,
+        };
+
+        let actual = to_authentication_error(error.into(), "acme");
+
+        match actual.downcast_ref::<UdemyError>() {
+            Some(UdemyError::Authentication { message }) => {
+                assert!(message.contains("portal acme"));
+                assert!(message.contains("403"));
+            }
+            _ => panic!("expected an authentication error"),
+        }
+    }
+
+    #[test]
+    fn authentication_error_for_other_status() {
+        let error = UdemyError::HttpStatus {
+            url: "the-url".into(),
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            detail: None,
+        };
+
+        let actual = to_authentication_error(error.into(), "www");
+
+        match actual.downcast_ref::<UdemyError>() {
+            Some(UdemyError::HttpStatus { status, .. }) => {
+                assert_eq!(*status, StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            _ => panic!("expected the original error"),
+        }
+    }
 }
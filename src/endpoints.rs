@@ -0,0 +1,211 @@
+use crate::model::*;
+
+/// Base url of the udemy api, `{portal}` being replaced by the portal name.
+pub const DEFAULT_API_BASE: &str = "https://{portal}.udemy.com/api-2.0";
+/// Environment variable overriding the base url of the api.
+pub const API_BASE_ENV: &str = "UDEMY_API_BASE";
+/// Portal of individual udemy accounts.
+pub const DEFAULT_PORTAL: &str = "www";
+
+/// Fields requested for each kind of object, as `fields[kind]=...` parameters.
+type FieldSet = &'static [(&'static str, &'static str)];
+
+const USER_FIELDS: FieldSet = &[("user", "id,title")];
+const SUBSCRIBED_COURSE_FIELDS: FieldSet = &[(
+    "course",
+    "id,url,published_title,title,visible_instructors,headline,image_480x270",
+)];
+const CURRICULUM_FIELDS: FieldSet = &[
+    (
+        "lecture",
+        "@min,object_index,asset,supplementary_assets,sort_order,is_published,is_free",
+    ),
+    ("quiz", "@min,object_index,title,sort_order,is_published"),
+    (
+        "practice",
+        "@min,object_index,title,sort_order,is_published",
+    ),
+    (
+        "chapter",
+        "@min,description,object_index,title,sort_order,is_published",
+    ),
+    (
+        "asset",
+        "@min,title,filename,asset_type,external_url,length,status",
+    ),
+];
+const LECTURE_FIELDS: FieldSet = &[
+    ("asset", "@min,download_urls,external_url,slide_urls,status,captions,thumbnail_url,time_estimation,stream_urls,body"),
+    ("caption", "@default,is_translation"),
+    ("course", "id,url,locale"),
+    ("lecture", "@default,course,can_give_cc_feedback,download_url"),
+];
+const SUPPLEMENTARY_ASSET_FIELDS: FieldSet = &[("asset", "download_urls")];
+const PROGRESS_FIELDS: FieldSet = &[("course", "completed_lecture_ids")];
+
+/// Builds the urls of the api endpoints used, out of a base url.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    base: String,
+}
+
+impl Endpoints {
+    pub fn new(api_base: &str, portal_name: &str) -> Endpoints {
+        Endpoints {
+            base: api_base
+                .replace("{portal}", portal_name)
+                .trim_end_matches('/')
+                .into(),
+        }
+    }
+
+    /// The authenticated user.
+    pub fn user(&self) -> String {
+        format!("{}/users/me/?{}", self.base, fields(USER_FIELDS))
+    }
+
+    /// The subscribed courses matching a search.
+    pub fn subscribed_courses(&self, search: &str) -> String {
+        format!(
+            "{}/users/me/subscribed-courses?{}&page=1&page_size=1000&ordering=-access_time&search={}",
+            self.base,
+            fields(SUBSCRIBED_COURSE_FIELDS),
+            search
+        )
+    }
+
+    /// All the chapters and lectures of a course.
+    pub fn curriculum(&self, course_id: CourseId) -> String {
+        format!(
+            "{}/courses/{}/subscriber-curriculum-items/?page_size=1400&{}",
+            self.base,
+            course_id,
+            fields(CURRICULUM_FIELDS)
+        )
+    }
+
+    /// The details of a lecture, including its download urls.
+    pub fn lecture(&self, course_id: CourseId, lecture_id: LectureId) -> String {
+        format!(
+            "{}/users/me/subscribed-courses/{}/lectures/{}?{}",
+            self.base,
+            course_id,
+            lecture_id,
+            fields(LECTURE_FIELDS)
+        )
+    }
+
+    /// A supplementary asset of a lecture, with its download urls.
+    pub fn supplementary_asset(
+        &self,
+        course_id: CourseId,
+        lecture_id: LectureId,
+        asset_id: u64,
+    ) -> String {
+        format!(
+            "{}/users/me/subscribed-courses/{}/lectures/{}/supplementary-assets/{}/?{}",
+            self.base,
+            course_id,
+            lecture_id,
+            asset_id,
+            fields(SUPPLEMENTARY_ASSET_FIELDS)
+        )
+    }
+
+    /// The lectures of a course completed by the user.
+    pub fn completed_lectures(&self, course_id: CourseId) -> String {
+        format!(
+            "{}/users/me/subscribed-courses/{}/completed-lectures/",
+            self.base, course_id
+        )
+    }
+
+    /// A lecture of a course completed by the user.
+    pub fn completed_lecture(&self, course_id: CourseId, lecture_id: LectureId) -> String {
+        format!("{}{}/", self.completed_lectures(course_id), lecture_id)
+    }
+
+    /// The progress of the user in a course.
+    pub fn progress(&self, course_id: CourseId) -> String {
+        format!(
+            "{}/users/me/subscribed-courses/{}/progress/?{}",
+            self.base,
+            course_id,
+            fields(PROGRESS_FIELDS)
+        )
+    }
+}
+
+/// Portal named by the host of an api base, the default portal when it is not
+/// a udemy host or leaves the portal to be replaced.
+pub fn portal_of_api_base(api_base: &str) -> String {
+    api_base
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|host| host.strip_suffix(".udemy.com"))
+        .filter(|portal| !portal.is_empty() && *portal != "{portal}")
+        .unwrap_or(DEFAULT_PORTAL)
+        .into()
+}
+
+fn fields(field_set: FieldSet) -> String {
+    field_set
+        .iter()
+        .map(|(kind, fields)| format!("fields[{}]={}", kind, fields))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_base() {
+        let endpoints = Endpoints::new(DEFAULT_API_BASE, "acme");
+
+        assert_eq!(
+            endpoints.user(),
+            "https://acme.udemy.com/api-2.0/users/me/?fields[user]=id,title"
+        );
+    }
+
+    #[test]
+    fn custom_base() {
+        let endpoints = Endpoints::new("http://localhost:8080/udemy/{portal}/", "www");
+
+        assert_eq!(
+            endpoints.completed_lecture(54321, 4321),
+            "http://localhost:8080/udemy/www/users/me/subscribed-courses/54321/completed-lectures/4321/"
+        );
+        assert_eq!(
+            endpoints.progress(54321),
+            "http://localhost:8080/udemy/www/users/me/subscribed-courses/54321/progress/?fields[course]=completed_lecture_ids"
+        );
+        assert_eq!(
+            endpoints.supplementary_asset(54321, 4321, 1),
+            "http://localhost:8080/udemy/www/users/me/subscribed-courses/54321/lectures/4321/supplementary-assets/1/?fields[asset]=download_urls"
+        );
+    }
+
+    #[test]
+    fn portal_of_bases() {
+        assert_eq!(portal_of_api_base(DEFAULT_API_BASE), "www");
+        assert_eq!(portal_of_api_base("https://acme.udemy.com/api-2.0"), "acme");
+        assert_eq!(
+            portal_of_api_base("http://localhost:8080/udemy/{portal}/"),
+            "www"
+        );
+    }
+
+    #[test]
+    fn curriculum_fields() {
+        let endpoints = Endpoints::new(DEFAULT_API_BASE, "www");
+
+        assert_eq!(
+            endpoints.curriculum(54321),
+            "https://www.udemy.com/api-2.0/courses/54321/subscriber-curriculum-items/?page_size=1400&fields[lecture]=@min,object_index,asset,supplementary_assets,sort_order,is_published,is_free&fields[quiz]=@min,object_index,title,sort_order,is_published&fields[practice]=@min,object_index,title,sort_order,is_published&fields[chapter]=@min,description,object_index,title,sort_order,is_published&fields[asset]=@min,title,filename,asset_type,external_url,length,status"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+use std::io;
+
+use failure::{Error, Fail};
+use indicatif::HumanBytes;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+/// Exit code for errors that do not fall in any other category.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code used when authentication to udemy failed.
+pub const EXIT_AUTHENTICATION: i32 = 2;
+/// Exit code used when the course is not part of the subscribed courses.
+pub const EXIT_NOT_SUBSCRIBED: i32 = 3;
+/// Exit code used when udemy answered with an unexpected http status.
+pub const EXIT_HTTP_STATUS: i32 = 4;
+/// Exit code used when udemy could not be reached.
+pub const EXIT_NETWORK: i32 = 5;
+/// Exit code used when a response from udemy could not be understood.
+pub const EXIT_PARSE: i32 = 6;
+/// Exit code used when reading or writing local files failed.
+pub const EXIT_FILESYSTEM: i32 = 7;
+/// Exit code used when some of the lectures could not be processed.
+pub const EXIT_PARTIAL_FAILURE: i32 = 8;
+/// Exit code used when another run holds the lock of the course directory.
+pub const EXIT_LOCKED: i32 = 9;
+/// Exit code used when the user interrupted the program, as shells do for SIGINT.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Errors that need to be told apart from generic failures.
+#[derive(Debug)]
+pub enum UdemyError {
+    Authentication {
+        message: String,
+    },
+    NotSubscribed {
+        course_name: String,
+    },
+    HttpStatus {
+        url: String,
+        status: StatusCode,
+        detail: Option<String>,
+    },
+    Network {
+        url: String,
+        message: String,
+    },
+    Parse {
+        path: String,
+        message: String,
+    },
+    Filesystem {
+        path: String,
+        message: String,
+    },
+    PartialFailure {
+        failed: usize,
+        total: usize,
+    },
+    InsufficientSpace {
+        path: String,
+        needed: u64,
+        available: u64,
+    },
+    Locked {
+        path: String,
+        pid: u32,
+        host: String,
+        started: String,
+    },
+    Interrupted,
+}
+
+impl UdemyError {
+    /// Exit code documented for this category of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            UdemyError::Authentication { .. } => EXIT_AUTHENTICATION,
+            UdemyError::NotSubscribed { .. } => EXIT_NOT_SUBSCRIBED,
+            UdemyError::HttpStatus { .. } => EXIT_HTTP_STATUS,
+            UdemyError::Network { .. } => EXIT_NETWORK,
+            UdemyError::Parse { .. } => EXIT_PARSE,
+            UdemyError::Filesystem { .. } => EXIT_FILESYSTEM,
+            UdemyError::PartialFailure { .. } => EXIT_PARTIAL_FAILURE,
+            UdemyError::InsufficientSpace { .. } => EXIT_FILESYSTEM,
+            UdemyError::Locked { .. } => EXIT_LOCKED,
+            UdemyError::Interrupted => EXIT_INTERRUPTED,
+        }
+    }
+}
+
+impl Fail for UdemyError {}
+
+impl fmt::Display for UdemyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UdemyError::Authentication { message } => write!(f, "{}", message),
+            UdemyError::NotSubscribed { course_name } => {
+                write!(f, "{} was not found in subscribed courses", course_name)
+            }
+            UdemyError::HttpStatus {
+                url,
+                status,
+                detail: None,
+            } => write!(f, "Error while getting from url <{}>: <{}>", url, status),
+            UdemyError::HttpStatus {
+                url,
+                status,
+                detail: Some(detail),
+            } => write!(
+                f,
+                "Error while getting from url <{}>: <{}>: {}",
+                url, status, detail
+            ),
+            UdemyError::Network { url, message } => {
+                write!(f, "Error while connecting to url <{}>: {}", url, message)
+            }
+            UdemyError::Parse { path, message } => {
+                write!(f, "Error parsing json ({}): {}", path, message)
+            }
+            UdemyError::Filesystem { path, message } => {
+                write!(f, "Error while accessing <{}>: {}", path, message)
+            }
+            UdemyError::PartialFailure { failed, total } => {
+                write!(f, "{} out of {} lectures failed", failed, total)
+            }
+            UdemyError::InsufficientSpace {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Not enough space in <{}>: {} needed, {} available",
+                path,
+                HumanBytes(*needed),
+                HumanBytes(*available)
+            ),
+            UdemyError::Locked {
+                path,
+                pid,
+                host,
+                started,
+            } => write!(
+                f,
+                "Another run (pid {} on {}, started {}) is downloading to the same course directory, \
+                 remove <{}> if that run is gone",
+                pid, host, started, path
+            ),
+            UdemyError::Interrupted => write!(f, "Interrupted by the user"),
+        }
+    }
+}
+
+/// Prefix the json path of a parse error, leaving other errors untouched.
+pub fn with_json_path(error: Error, prefix: &str) -> Error {
+    match error.downcast::<UdemyError>() {
+        Ok(UdemyError::Parse { path, message }) => UdemyError::Parse {
+            path: format!("{}.{}", prefix, path),
+            message,
+        }
+        .into(),
+        Ok(error) => error.into(),
+        Err(error) => error,
+    }
+}
+
+/// Extract the message out of an error body sent by udemy. Such bodies hold either
+/// a `detail` string or an `error` being a string or an object with a message.
+pub fn parse_error_body(body: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let message = match value.get("detail") {
+        Some(Value::String(detail)) => Some(detail.as_str()),
+        _ => match value.get("error") {
+            Some(Value::String(error)) => Some(error.as_str()),
+            Some(error) => error
+                .get("message")
+                .or_else(|| error.get("detail"))
+                .and_then(Value::as_str),
+            None => None,
+        },
+    };
+    message
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .map(String::from)
+}
+
+/// Exit code to use when the program terminates because of error.
+pub fn exit_code(error: &Error) -> i32 {
+    if let Some(error) = error.downcast_ref::<UdemyError>() {
+        error.exit_code()
+    } else if error.downcast_ref::<reqwest::Error>().is_some() {
+        EXIT_NETWORK
+    } else if error.downcast_ref::<serde_json::Error>().is_some() {
+        EXIT_PARSE
+    } else if error.downcast_ref::<io::Error>().is_some() {
+        EXIT_FILESYSTEM
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+#[cfg(test)]
+mod test_error {
+    use super::*;
+
+    use failure::format_err;
+
+    #[test]
+    fn exit_code_for_udemy_errors() {
+        let error: Error = UdemyError::NotSubscribed {
+            course_name: "the-course".into(),
+        }
+        .into();
+
+        assert_eq!(exit_code(&error), EXIT_NOT_SUBSCRIBED);
+    }
+
+    #[test]
+    fn exit_code_for_io_errors() {
+        let error: Error = io::Error::new(io::ErrorKind::NotFound, "not found").into();
+
+        assert_eq!(exit_code(&error), EXIT_FILESYSTEM);
+    }
+
+    #[test]
+    fn exit_code_for_other_errors() {
+        let error = format_err!("Something went wrong");
+
+        assert_eq!(exit_code(&error), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn error_body_with_detail() {
+        let actual =
+            parse_error_body(r#"{"detail": "You do not have permission to perform this action."}"#);
+
+        assert_eq!(
+            actual,
+            Some("You do not have permission to perform this action.".into())
+        );
+    }
+
+    #[test]
+    fn error_body_with_error() {
+        assert_eq!(
+            parse_error_body(r#"{"error": "Invalid lecture"}"#),
+            Some("Invalid lecture".into())
+        );
+        assert_eq!(
+            parse_error_body(r#"{"error": {"code": 404, "message": "Not found."}}"#),
+            Some("Not found.".into())
+        );
+    }
+
+    #[test]
+    fn error_body_without_message() {
+        assert_eq!(parse_error_body("<html>Bad Gateway</html>"), None);
+        assert_eq!(parse_error_body(r#"{"detail": ""}"#), None);
+        assert_eq!(parse_error_body(r#"{"results": []}"#), None);
+    }
+
+    #[test]
+    fn http_status_with_detail() {
+        let error = UdemyError::HttpStatus {
+            url: "the-url".into(),
+            status: StatusCode::NOT_FOUND,
+            detail: Some("Not found.".into()),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "Error while getting from url <the-url>: <404 Not Found>: Not found."
+        );
+    }
+
+    #[test]
+    fn json_path_is_prefixed() {
+        let error: Error = UdemyError::Parse {
+            path: "title".into(),
+            message: "missing".into(),
+        }
+        .into();
+
+        let actual = with_json_path(with_json_path(error, "asset"), "results[3]");
+
+        assert_eq!(
+            actual.to_string(),
+            "Error parsing json (results[3].asset.title): missing"
+        );
+        assert_eq!(exit_code(&actual), EXIT_PARSE);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::model::*;
+use crate::result::Result;
+use crate::summary::{Status, Summary};
+
+/// Default name of the file listing the lectures that failed during the last run.
+pub const FAILURES_FILENAME: &str = "failures.json";
+
+/// A lecture that failed during a run, as saved in the failures file.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Failure {
+    pub course: String,
+    pub chapter: ObjectIndex,
+    pub lecture: ObjectIndex,
+    pub lecture_id: LectureId,
+    pub title: String,
+    pub error: String,
+}
+
+/// Collect the failed lectures of a summary.
+pub fn failures_from_summary(course_name: &str, summary: &Summary) -> Vec<Failure> {
+    summary
+        .outcomes
+        .iter()
+        .filter(|outcome| outcome.status == Status::Failed)
+        .map(|outcome| Failure {
+            course: course_name.into(),
+            chapter: outcome.chapter_index,
+            lecture: outcome.lecture_index,
+            lecture_id: outcome.lecture_id,
+            title: outcome.title.clone(),
+            error: outcome.reason.clone().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Update previous failures with a run of a course: failures of its lectures
+/// attempted again are replaced by those of the run, others are kept as they
+/// are, those of other courses included.
+pub fn merge_failures(
+    course_name: &str,
+    previous_failures: Vec<Failure>,
+    summary: &Summary,
+) -> Vec<Failure> {
+    let attempted: HashSet<LectureId> = summary
+        .outcomes
+        .iter()
+        .map(|outcome| outcome.lecture_id)
+        .collect();
+    let mut failures: Vec<Failure> = previous_failures
+        .into_iter()
+        .filter(|failure| failure.course != course_name || !attempted.contains(&failure.lecture_id))
+        .collect();
+    failures.append(&mut failures_from_summary(course_name, summary));
+    failures
+}
+
+pub fn parse_failures(content: &str) -> Result<Vec<Failure>> {
+    Ok(serde_json::from_str(content)?)
+}
+
+pub fn failures_to_json(failures: &[Failure]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(failures)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::summary::LectureOutcome;
+    use crate::test_data::test::*;
+
+    #[test]
+    fn failures_from_summary_keeps_failed() {
+        let course_content = make_test_course_content();
+        let chapter = &course_content.chapters[0];
+        let lecture = &chapter.lectures[0];
+        let mut summary = Summary::new();
+        summary.push(LectureOutcome::new(chapter, lecture, Status::Downloaded));
+        summary.push(LectureOutcome::new(chapter, lecture, Status::Failed).with_reason("boom"));
+
+        let actual = failures_from_summary("the-course", &summary);
+
+        assert_eq!(
+            actual,
+            vec![Failure {
+                course: "the-course".into(),
+                chapter: 1,
+                lecture: 1,
+                lecture_id: 4321,
+                title: "The Lecture".into(),
+                error: "boom".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn merge_failures_keeps_other_courses() {
+        let course_content = make_test_course_content();
+        let chapter = &course_content.chapters[0];
+        let lecture = &chapter.lectures[0];
+        let failure = |course: &str, lecture_id: LectureId| Failure {
+            course: course.into(),
+            chapter: 1,
+            lecture: 1,
+            lecture_id,
+            title: "The Lecture".into(),
+            error: "boom".into(),
+        };
+        let previous_failures = vec![
+            failure("the-course", 4321),
+            failure("the-course", 1234),
+            failure("other-course", 4321),
+        ];
+        let mut summary = Summary::new();
+        summary.push(LectureOutcome::new(chapter, lecture, Status::Downloaded));
+
+        let actual = merge_failures("the-course", previous_failures, &summary);
+
+        assert_eq!(
+            actual,
+            vec![failure("the-course", 1234), failure("other-course", 4321)]
+        );
+    }
+
+    #[test]
+    fn failures_round_trip() {
+        let failures = vec![Failure {
+            course: "the-course".into(),
+            chapter: 2,
+            lecture: 12,
+            lecture_id: 1234,
+            title: "The Lecture".into(),
+            error: "boom".into(),
+        }];
+
+        let actual = parse_failures(failures_to_json(&failures).unwrap().as_str());
+
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), failures);
+    }
+}
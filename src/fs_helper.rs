@@ -1,15 +1,96 @@
+use crate::error::UdemyError;
 use crate::result::Result;
-use std::fs::DirBuilder;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::Path;
 
 pub trait FsHelper {
     fn create_dir_recursive(&self, path: &str) -> Result<()>;
+    fn write_file(&self, path: &str, data: &[u8]) -> Result<()>;
+    fn read_file(&self, path: &str) -> Result<String>;
+    fn rename_file(&self, from: &str, to: &str) -> Result<()>;
+    /// Create a file only if it does not exist yet, telling whether it was created.
+    fn create_new_file(&self, path: &str, data: &[u8]) -> Result<bool>;
+    fn remove_file(&self, path: &str) -> Result<()>;
+    fn file_exists(&self, path: &str) -> bool;
+    /// Names of the entries of a directory.
+    fn list_dir(&self, path: &str) -> Result<Vec<String>>;
+    /// Free space available to the user on the filesystem of a path, which
+    /// may not exist yet.
+    fn available_space(&self, path: &str) -> Result<u64>;
 }
 
 pub struct UdemyFsHelper {}
 
 impl FsHelper for UdemyFsHelper {
     fn create_dir_recursive(&self, path: &str) -> Result<()> {
-        DirBuilder::new().recursive(true).create(path)?;
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .map_err(|e| filesystem_error(path, &e))?;
         Ok(())
     }
+
+    fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        let mut file = File::create(path).map_err(|e| filesystem_error(path, &e))?;
+        file.write_all(data)
+            .map_err(|e| filesystem_error(path, &e))?;
+        Ok(())
+    }
+
+    fn read_file(&self, path: &str) -> Result<String> {
+        Ok(fs::read_to_string(path).map_err(|e| filesystem_error(path, &e))?)
+    }
+
+    fn rename_file(&self, from: &str, to: &str) -> Result<()> {
+        fs::rename(from, to).map_err(|e| filesystem_error(to, &e))?;
+        Ok(())
+    }
+
+    fn create_new_file(&self, path: &str, data: &[u8]) -> Result<bool> {
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(filesystem_error(path, &e).into()),
+        };
+        file.write_all(data)
+            .map_err(|e| filesystem_error(path, &e))?;
+        Ok(true)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        fs::remove_file(path).map_err(|e| filesystem_error(path, &e))?;
+        Ok(())
+    }
+
+    fn file_exists(&self, path: &str) -> bool {
+        Path::new(path).is_file()
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<String>> {
+        fs::read_dir(path)
+            .map_err(|e| filesystem_error(path, &e))?
+            .map(|entry| {
+                entry
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .map_err(|e| filesystem_error(path, &e).into())
+            })
+            .collect()
+    }
+
+    fn available_space(&self, path: &str) -> Result<u64> {
+        let existing = Path::new(path)
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        Ok(fs2::available_space(existing).map_err(|e| filesystem_error(path, &e))?)
+    }
+}
+
+fn filesystem_error(path: &str, error: &std::io::Error) -> UdemyError {
+    UdemyError::Filesystem {
+        path: path.into(),
+        message: error.to_string(),
+    }
 }
@@ -1,17 +1,28 @@
 use crate::command::*;
 use std::any::Any;
+use std::collections::HashSet;
+
+use indicatif::HumanBytes;
 
 use crate::model::*;
+use crate::outline::{Outline, OutputFormat};
 use crate::result::Result;
+use crate::summary::Summary;
+use crate::utils::format_duration;
 
 pub struct InfoParams {
     pub verbose: bool,
+    pub sizes: bool,
+    pub format: OutputFormat,
 }
 
 pub struct Info {
     params: Option<InfoParams>,
 }
 
+/// Byte size of each available quality label, in the order they were found.
+type Sizes = Vec<(String, u64)>;
+
 impl Info {
     pub fn new() -> Info {
         Info { params: None }
@@ -23,25 +34,188 @@ impl Command for Info {
         if let Some(params) = params.downcast_ref::<InfoParams>() {
             self.params = Some(InfoParams {
                 verbose: params.verbose,
+                sizes: params.sizes,
+                format: params.format,
             });
         }
     }
 
-    fn execute(&self, context: &CommandContext) -> Result<()> {
-        self.print_course_content(context.course_content.as_ref().unwrap());
-        Ok(())
+    fn execute(&self, context: &CommandContext) -> Result<Summary> {
+        let course_content = context.course_content.as_ref().unwrap();
+        let format = self
+            .params
+            .as_ref()
+            .map_or(OutputFormat::Text, |params| params.format);
+        // The outline is still worth showing without the progress.
+        let completed = match context.get_completed_lecture_ids() {
+            Ok(completed) => Some(completed),
+            Err(e) => {
+                eprintln!("Could not get the progress, completion is unknown: {}", e);
+                None
+            }
+        };
+        if format == OutputFormat::Text {
+            self.print_course_content(context, course_content, completed.as_ref());
+        } else {
+            let course = context.course.as_ref().unwrap();
+            println!(
+                "{}",
+                Outline::new(course, course_content, completed.as_ref()).render(format)?
+            );
+        }
+        Ok(Summary::new())
     }
 }
 
 impl Info {
-    fn print_course_content(&self, course_content: &CourseContent) {
+    fn print_course_content(
+        &self,
+        context: &CommandContext,
+        course_content: &CourseContent,
+        completed: Option<&HashSet<LectureId>>,
+    ) {
+        let (verbose, sizes) = match self.params {
+            Some(ref params) => (params.verbose, params.sizes),
+            None => (false, false),
+        };
+        let mut course_sizes = Sizes::new();
         for chapter in course_content.chapters.iter() {
-            println!("{:03} Chapter {}", chapter.object_index, chapter.title);
+            println!(
+                "{:03} Chapter {} ({} lectures, {})",
+                chapter.object_index,
+                chapter.title,
+                chapter.lectures.len(),
+                format_duration(chapter_duration(chapter))
+            );
+            let mut chapter_sizes = Sizes::new();
             for lecture in chapter.lectures.iter() {
-                println!("\t{:03} Lecture {}", lecture.object_index, lecture.title);
+                println!(
+                    "\t{:03} {} Lecture {} [{}{}]{}",
+                    lecture.object_index,
+                    match completed {
+                        Some(completed) if completed.contains(&lecture.id) => "done   ",
+                        Some(_) => "pending",
+                        None => "?      ",
+                    },
+                    lecture.title,
+                    lecture.asset_type,
+                    if lecture.duration > 0 {
+                        format!(" {}", format_duration(lecture.duration))
+                    } else {
+                        String::new()
+                    },
+                    match lecture.supplementary_assets.len() {
+                        0 => String::new(),
+                        1 => " +1 supplementary asset".into(),
+                        count => format!(" +{} supplementary assets", count),
+                    }
+                );
+                if sizes {
+                    match lecture_sizes(context, lecture) {
+                        Ok(lecture_sizes) => {
+                            if !lecture_sizes.is_empty() {
+                                println!("\t\t{}", format_sizes(&lecture_sizes));
+                            }
+                            add_sizes(&mut chapter_sizes, &lecture_sizes);
+                        }
+                        Err(e) => {
+                            if verbose {
+                                eprintln!("\t\tCould not estimate sizes: {}", e);
+                            }
+                        }
+                    }
+                }
+            }
+            if sizes && !chapter_sizes.is_empty() {
+                println!("\tChapter sizes: {}", format_sizes(&chapter_sizes));
+            }
+            add_sizes(&mut course_sizes, &chapter_sizes);
+        }
+        println!(
+            "Total: {} chapters, {} lectures, {}",
+            course_content.chapters.len(),
+            course_content
+                .chapters
+                .iter()
+                .map(|chapter| chapter.lectures.len())
+                .sum::<usize>(),
+            format_duration(course_content.chapters.iter().map(chapter_duration).sum())
+        );
+        if sizes && !course_sizes.is_empty() {
+            println!("Course sizes: {}", format_sizes(&course_sizes));
+        }
+        match completed {
+            Some(completed) => {
+                let (done, total, remaining) = progress(course_content, completed);
+                println!(
+                    "Progress: {}/{} lectures completed ({}%), {} of video remaining",
+                    done,
+                    total,
+                    (done * 100).checked_div(total).unwrap_or(0),
+                    format_duration(remaining)
+                );
             }
+            None => println!("Progress: unknown"),
+        }
+    }
+}
+
+fn chapter_duration(chapter: &Chapter) -> u64 {
+    chapter
+        .lectures
+        .iter()
+        .map(|lecture| lecture.duration)
+        .sum()
+}
+
+/// Completed and total lectures, along with the duration of the pending ones.
+fn progress(course_content: &CourseContent, completed: &HashSet<LectureId>) -> (usize, usize, u64) {
+    let lectures = course_content
+        .chapters
+        .iter()
+        .flat_map(|chapter| chapter.lectures.iter());
+    let (mut done, mut total, mut remaining) = (0, 0, 0);
+    for lecture in lectures {
+        total += 1;
+        if completed.contains(&lecture.id) {
+            done += 1;
+        } else {
+            remaining += lecture.duration;
+        }
+    }
+    (done, total, remaining)
+}
+
+/// Probe the size of every download available for a lecture.
+fn lecture_sizes(context: &CommandContext, lecture: &Lecture) -> Result<Sizes> {
+    let lecture_detail = context.get_lecture_detail(lecture)?;
+    let mut sizes = Sizes::new();
+    if let Some(download_urls) = lecture_detail.asset.download_urls {
+        for download_url in download_urls.iter() {
+            let size = context
+                .client
+                .get_content_length(download_url.file.as_str())?;
+            add_sizes(&mut sizes, &[(download_url.label.clone(), size)]);
         }
     }
+    Ok(sizes)
+}
+
+fn add_sizes(total: &mut Sizes, sizes: &[(String, u64)]) {
+    for (label, size) in sizes.iter() {
+        match total.iter_mut().find(|(l, _)| l == label) {
+            Some((_, total_size)) => *total_size += size,
+            None => total.push((label.clone(), *size)),
+        }
+    }
+}
+
+fn format_sizes(sizes: &[(String, u64)]) -> String {
+    sizes
+        .iter()
+        .map(|(label, size)| format!("{}: {}", label, HumanBytes(*size)))
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
 #[cfg(test)]
@@ -56,6 +230,95 @@ mod test {
 
     #[test]
     fn info() {
+        let _mocks = lock_mocks();
+        unsafe {
+            GETS_AS_JSON_URL = Some(vec![]);
+        }
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+        let downloader = UdemyDownloader::new(&mut context);
+
+        let mut info = Info::new();
+        info.set_params(&InfoParams {
+            verbose: true,
+            sizes: false,
+            format: OutputFormat::Text,
+        });
+
+        let result = downloader.execute(&info);
+
+        assert!(result.is_ok());
+        unsafe {
+            if let Some(ref gaj) = GETS_AS_JSON_URL {
+                assert_eq!(gaj.len(), 1);
+                assert_eq!(gaj[0], "https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/progress/?fields[course]=completed_lecture_ids");
+            }
+        }
+    }
+
+    #[test]
+    fn info_without_progress() {
+        let _mocks = lock_mocks();
+        unsafe {
+            GETS_AS_JSON_URL = Some(vec![]);
+            FAILING_URLS = Some(vec!["https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/progress/?fields[course]=completed_lecture_ids".into()]);
+        }
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.course = Some(make_course());
+        context.course_content = Some(make_test_course_content());
+        let downloader = UdemyDownloader::new(&mut context);
+
+        for format in &[OutputFormat::Text, OutputFormat::Json] {
+            let mut info = Info::new();
+            info.set_params(&InfoParams {
+                verbose: false,
+                sizes: false,
+                format: *format,
+            });
+
+            assert!(downloader.execute(&info).is_ok());
+        }
+    }
+
+    #[test]
+    fn info_with_sizes() {
+        let _mocks = lock_mocks();
+        unsafe {
+            PARSE = Some(vec![]);
+            GETS_AS_JSON_URL = Some(vec![]);
+            GETS_CONTENT_LENGTH_URL = Some(vec![]);
+        }
         let fs_helper = MockFsHelper {};
 
         let mock_http_client = MockHttpClient {};
@@ -77,10 +340,45 @@ mod test {
         let downloader = UdemyDownloader::new(&mut context);
 
         let mut info = Info::new();
-        info.set_params(&InfoParams { verbose: true });
+        info.set_params(&InfoParams {
+            verbose: true,
+            sizes: true,
+            format: OutputFormat::Text,
+        });
 
         let result = downloader.execute(&info);
 
         assert!(result.is_ok());
+        unsafe {
+            if let Some(ref gcl) = GETS_CONTENT_LENGTH_URL {
+                assert_eq!(gcl.len(), 1);
+                assert_eq!(gcl[0], "http://host-name/the-filename.mp4");
+            }
+        }
+    }
+
+    #[test]
+    fn progress_counts_pending_duration() {
+        let course_content = make_test_course_content();
+
+        assert_eq!(progress(&course_content, &HashSet::new()), (0, 1, 147));
+        assert_eq!(
+            progress(&course_content, &vec![4321].into_iter().collect()),
+            (1, 1, 0)
+        );
+    }
+
+    #[test]
+    fn sizes_are_added_per_label() {
+        let mut total = Sizes::new();
+
+        add_sizes(&mut total, &[("720".into(), 10), ("480".into(), 5)]);
+        add_sizes(&mut total, &[("480".into(), 2), ("1080".into(), 20)]);
+
+        assert_eq!(
+            total,
+            vec![("720".into(), 10), ("480".into(), 7), ("1080".into(), 20)]
+        );
+        assert_eq!(format_sizes(&total[..1]), "720: 10B");
     }
 }
@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use failure::format_err;
+
+use crate::error::EXIT_INTERRUPTED;
+use crate::result::Result;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle Ctrl-C and termination requests. The first one asks running work to
+/// stop once the current chunk is received, the second one exits right away.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if is_interrupted() {
+            eprintln!("\nAborted");
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        interrupt();
+        eprintln!("\nInterrupted, stopping after the current chunk (Ctrl-C again to abort)");
+    })
+    .map_err(|e| format_err!("Could not handle interruptions: {}", e))
+}
+
+/// Flag set when the user asks to stop, handed to running commands.
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+/// Ask running work to stop.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Tell whether the user asked to stop, so that no new work is to be started.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mocks::test::lock_mocks;
+
+    #[test]
+    fn interrupt_and_clear() {
+        // Commands of other tests read the same flag.
+        let _mocks = lock_mocks();
+        assert!(!is_interrupted());
+
+        interrupt();
+        assert!(is_interrupted());
+
+        clear();
+        assert!(!is_interrupted());
+    }
+}
@@ -0,0 +1,453 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::UdemyError;
+use crate::result::Result;
+use crate::udemy_helper::UdemyHelper;
+
+/// Name of the lock file in the course directory.
+pub const LOCK_FILENAME: &str = ".udemy-dl.lock";
+/// Age after which the lock of a run on another host is deemed stale, its
+/// process not being observable from here.
+const STALE_LOCK_HOURS: i64 = 24;
+/// Host recorded when its name cannot be found, which does not tell two hosts
+/// apart.
+const UNKNOWN_HOST: &str = "unknown";
+
+/// Run holding the lock of a course directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    pub started: String,
+}
+
+impl LockOwner {
+    fn current() -> LockOwner {
+        LockOwner {
+            pid: std::process::id(),
+            host: host_name(),
+            started: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Advisory lock on a course directory, preventing two runs from writing the
+/// same files. The lock is released when dropped.
+pub struct CourseLock<'a> {
+    udemy_helper: &'a UdemyHelper<'a>,
+    path: String,
+}
+
+impl<'a> CourseLock<'a> {
+    /// Lock a course directory, taking over locks left by runs that are gone.
+    pub fn acquire(udemy_helper: &'a UdemyHelper<'a>, course_dir: &str) -> Result<CourseLock<'a>> {
+        let mut path_buf = PathBuf::from(course_dir);
+        path_buf.push(LOCK_FILENAME);
+        let path: String = path_buf.to_string_lossy().into();
+        let owner = LockOwner::current();
+        let data = serde_json::to_string(&owner)?;
+
+        if !udemy_helper.create_new_file(path.as_str(), data.as_bytes())? {
+            // Released meanwhile when it cannot be read, to be created again.
+            let text = udemy_helper.read_file(path.as_str()).unwrap_or_default();
+            if let Ok(existing) = serde_json::from_str::<LockOwner>(text.as_str()) {
+                if !is_stale(
+                    &existing,
+                    owner.host.as_str(),
+                    Utc::now(),
+                    &is_process_alive,
+                ) {
+                    return Err(locked_error(path.as_str(), existing));
+                }
+                eprintln!(
+                    "Removing stale lock of pid {} on {} since {}",
+                    existing.pid, existing.host, existing.started
+                );
+            }
+            take_over(udemy_helper, path.as_str(), text.as_str(), &owner)?;
+            if !udemy_helper.create_new_file(path.as_str(), data.as_bytes())? {
+                return Err(current_locked_error(udemy_helper, path.as_str()));
+            }
+        }
+        Ok(CourseLock { udemy_helper, path })
+    }
+}
+
+/// Remove a stale lock, unless another run took it over since it was read.
+/// The lock is first moved aside, which only one run can do, and put back if it
+/// is not the stale one anymore.
+fn take_over(udemy_helper: &UdemyHelper, path: &str, stale: &str, owner: &LockOwner) -> Result<()> {
+    let aside = format!("{}.{}", path, owner.pid);
+    if udemy_helper.rename_file(path, aside.as_str()).is_err() {
+        // Moved by another run, which is now to be found holding the lock.
+        return Ok(());
+    }
+    let moved = udemy_helper.read_file(aside.as_str())?;
+    if moved != stale {
+        udemy_helper.create_new_file(path, moved.as_bytes())?;
+        udemy_helper.remove_file(aside.as_str())?;
+        return Err(current_locked_error(udemy_helper, path));
+    }
+    udemy_helper.remove_file(aside.as_str())
+}
+
+impl<'a> Drop for CourseLock<'a> {
+    fn drop(&mut self) {
+        if let Err(e) = self.udemy_helper.remove_file(self.path.as_str()) {
+            eprintln!("Could not release lock: {}", e);
+        }
+    }
+}
+
+fn current_locked_error(udemy_helper: &UdemyHelper, path: &str) -> failure::Error {
+    match udemy_helper
+        .read_file(path)
+        .and_then(|text| Ok(serde_json::from_str::<LockOwner>(text.as_str())?))
+    {
+        Ok(existing) => locked_error(path, existing),
+        Err(e) => e,
+    }
+}
+
+fn locked_error(path: &str, owner: LockOwner) -> failure::Error {
+    UdemyError::Locked {
+        path: path.into(),
+        pid: owner.pid,
+        host: owner.host,
+        started: owner.started,
+    }
+    .into()
+}
+
+/// Tell whether a lock was left by a run that is gone: on this host when its
+/// process does not exist anymore, on other or unknown hosts, or when processes
+/// cannot be checked, when it is too old.
+pub fn is_stale(
+    owner: &LockOwner,
+    host: &str,
+    now: DateTime<Utc>,
+    is_alive: &dyn Fn(u32) -> Option<bool>,
+) -> bool {
+    let alive = if owner.host == host && host != UNKNOWN_HOST {
+        is_alive(owner.pid)
+    } else {
+        None
+    };
+    match alive {
+        Some(alive) => !alive,
+        None => match DateTime::parse_from_rfc3339(owner.started.as_str()) {
+            Ok(started) => now.signed_duration_since(started) > Duration::hours(STALE_LOCK_HOURS),
+            Err(_) => true,
+        },
+    }
+}
+
+/// Tell whether a process of this host exists, signal 0 only checking it can
+/// be sent one. A process of another user is alive as well.
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> Option<bool> {
+    use std::convert::TryFrom;
+
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return Some(false),
+    };
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    Some(std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+/// Tell whether a process of this host is still running. A process that
+/// cannot be opened for lack of rights is alive.
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> Option<bool> {
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::shared::winerror::ERROR_ACCESS_DENIED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if handle.is_null() {
+            return Some(GetLastError() == ERROR_ACCESS_DENIED);
+        }
+        let mut exit_code: DWORD = 0;
+        let queried = GetExitCodeProcess(handle, &mut exit_code);
+        CloseHandle(handle);
+        if queried == 0 {
+            return None;
+        }
+        Some(exit_code == STILL_ACTIVE)
+    }
+}
+
+/// Processes cannot be checked, the age of the lock tells instead.
+#[cfg(not(any(unix, windows)))]
+fn is_process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
+fn host_name() -> String {
+    system_host_name()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| UNKNOWN_HOST.into())
+}
+
+#[cfg(unix)]
+fn system_host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    // Truncated names may lack the terminating nul.
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(windows)]
+fn system_host_name() -> Option<String> {
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::winbase::{GetComputerNameW, MAX_COMPUTERNAME_LENGTH};
+
+    let mut buf = [0u16; MAX_COMPUTERNAME_LENGTH as usize + 1];
+    let mut len = buf.len() as DWORD;
+    if unsafe { GetComputerNameW(buf.as_mut_ptr(), &mut len) } == 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buf[..len as usize]))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn system_host_name() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use crate::error::UdemyError;
+    use crate::fs_helper::FsHelper;
+    use crate::mocks::test::*;
+
+    /// Files in memory, another run being able to take the lock over when it
+    /// is moved aside.
+    struct MemoryFsHelper {
+        files: RefCell<HashMap<String, String>>,
+        taken_over_by: Option<String>,
+    }
+
+    impl FsHelper for MemoryFsHelper {
+        fn create_dir_recursive(&self, _path: &str) -> Result<()> {
+            Ok(())
+        }
+        fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+            self.files
+                .borrow_mut()
+                .insert(path.into(), String::from_utf8_lossy(data).into_owned());
+            Ok(())
+        }
+        fn read_file(&self, path: &str) -> Result<String> {
+            self.files
+                .borrow()
+                .get(path)
+                .cloned()
+                .ok_or_else(|| failure::format_err!("No file {}", path))
+        }
+        fn rename_file(&self, from: &str, to: &str) -> Result<()> {
+            let mut files = self.files.borrow_mut();
+            if let Some(taken_over_by) = &self.taken_over_by {
+                files.insert(from.into(), taken_over_by.clone());
+            }
+            let data = files
+                .remove(from)
+                .ok_or_else(|| failure::format_err!("No file {}", from))?;
+            files.insert(to.into(), data);
+            Ok(())
+        }
+        fn create_new_file(&self, path: &str, data: &[u8]) -> Result<bool> {
+            if self.files.borrow().contains_key(path) {
+                return Ok(false);
+            }
+            self.write_file(path, data)?;
+            Ok(true)
+        }
+        fn remove_file(&self, path: &str) -> Result<()> {
+            self.files.borrow_mut().remove(path);
+            Ok(())
+        }
+        fn file_exists(&self, path: &str) -> bool {
+            self.files.borrow().contains_key(path)
+        }
+        fn list_dir(&self, _path: &str) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+        fn available_space(&self, _path: &str) -> Result<u64> {
+            Ok(u64::MAX)
+        }
+    }
+
+    const LOCK_PATH: &str = "~/Downloads/the-course/.udemy-dl.lock";
+
+    fn locked_fs(taken_over_by: Option<LockOwner>) -> MemoryFsHelper {
+        let stale = owner("laptop", "2019-09-01T12:00:00+00:00");
+        let mut files = HashMap::new();
+        files.insert(LOCK_PATH.into(), serde_json::to_string(&stale).unwrap());
+        MemoryFsHelper {
+            files: RefCell::new(files),
+            taken_over_by: taken_over_by.map(|owner| serde_json::to_string(&owner).unwrap()),
+        }
+    }
+
+    fn owner(host: &str, started: &str) -> LockOwner {
+        LockOwner {
+            pid: 1234,
+            host: host.into(),
+            started: started.into(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2019-09-07T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn stale_on_same_host() {
+        let owner = owner("nas", "2019-09-07T11:00:00+00:00");
+
+        assert!(is_stale(&owner, "nas", now(), &|_| Some(false)));
+        assert!(!is_stale(&owner, "nas", now(), &|pid| Some(pid == 1234)));
+    }
+
+    #[test]
+    fn stale_when_process_unknown() {
+        assert!(!is_stale(
+            &owner("nas", "2019-09-07T11:00:00+00:00"),
+            "nas",
+            now(),
+            &|_| None
+        ));
+        assert!(is_stale(
+            &owner("nas", "2019-09-06T11:00:00+00:00"),
+            "nas",
+            now(),
+            &|_| None
+        ));
+    }
+
+    #[test]
+    fn stale_on_unknown_hosts() {
+        // The process may be one of another host, whose name was not found either.
+        assert!(!is_stale(
+            &owner("unknown", "2019-09-07T11:00:00+00:00"),
+            "unknown",
+            now(),
+            &|_| Some(false)
+        ));
+        assert!(is_stale(
+            &owner("unknown", "2019-09-06T11:00:00+00:00"),
+            "unknown",
+            now(),
+            &|_| Some(true)
+        ));
+    }
+
+    #[test]
+    fn host_name_from_system() {
+        assert_ne!(host_name(), UNKNOWN_HOST);
+    }
+
+    #[test]
+    fn current_process_is_alive() {
+        assert_eq!(is_process_alive(std::process::id()), Some(true));
+    }
+
+    #[test]
+    fn stale_on_other_host() {
+        assert!(!is_stale(
+            &owner("laptop", "2019-09-07T01:00:00+00:00"),
+            "nas",
+            now(),
+            &|_| Some(true)
+        ));
+        assert!(is_stale(
+            &owner("laptop", "2019-09-06T01:00:00+00:00"),
+            "nas",
+            now(),
+            &|_| Some(true)
+        ));
+        assert!(is_stale(
+            &owner("laptop", "yesterday"),
+            "nas",
+            now(),
+            &|_| Some(true)
+        ));
+    }
+
+    #[test]
+    fn acquire_and_release() {
+        let _mocks = lock_mocks();
+        unsafe {
+            REMOVE_FILE_PATH = Some(vec![]);
+        }
+        let fs_helper = MockFsHelper {};
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+
+        let lock = CourseLock::acquire(&udemy_helper, "~/Downloads/the-course");
+
+        assert!(lock.is_ok());
+        drop(lock);
+        unsafe {
+            if let Some(ref rfp) = REMOVE_FILE_PATH {
+                assert_eq!(rfp.len(), 1);
+                assert_eq!(rfp[0], "~/Downloads/the-course/.udemy-dl.lock");
+            }
+        }
+    }
+
+    #[test]
+    fn acquire_stale_lock() {
+        let fs_helper = locked_fs(None);
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+
+        let lock = CourseLock::acquire(&udemy_helper, "~/Downloads/the-course").unwrap();
+
+        let text = fs_helper.read_file(LOCK_PATH).unwrap();
+        let owner = serde_json::from_str::<LockOwner>(text.as_str()).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        assert_eq!(fs_helper.files.borrow().len(), 1);
+        drop(lock);
+        assert!(fs_helper.files.borrow().is_empty());
+    }
+
+    #[test]
+    fn acquire_lock_taken_over_meanwhile() {
+        let fs_helper = locked_fs(Some(owner("laptop", &Utc::now().to_rfc3339())));
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+
+        let lock = CourseLock::acquire(&udemy_helper, "~/Downloads/the-course");
+
+        match lock.err().unwrap().downcast_ref::<UdemyError>() {
+            Some(UdemyError::Locked { host, .. }) => assert_eq!(host, "laptop"),
+            _ => panic!("expected a locked error"),
+        }
+        let text = fs_helper.read_file(LOCK_PATH).unwrap();
+        assert!(!text.contains("2019-09-01"));
+        assert_eq!(fs_helper.files.borrow().len(), 1);
+    }
+}
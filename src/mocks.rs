@@ -1,8 +1,12 @@
 #[cfg(test)]
 pub mod test {
 
+    use std::sync::{Mutex, MutexGuard};
+
+    use reqwest::StatusCode;
     use serde_json::{to_string, Value};
 
+    use crate::error::UdemyError;
     use crate::fs_helper::FsHelper;
     use crate::http_client::HttpClient;
     use crate::model::*;
@@ -12,9 +16,44 @@ pub mod test {
     pub static mut GETS_AS_JSON_URL: Option<Vec<String>> = None;
     pub static mut GETS_CONTENT_LENGTH_URL: Option<Vec<String>> = None;
     pub static mut GETS_AS_DATA_URL: Option<Vec<String>> = None;
+    /// Urls answering 403 once, after 100 bytes, as an expired signed url would.
+    pub static mut GETS_AS_DATA_EXPIRED: Option<Vec<String>> = None;
     pub static mut POST_JSON_DATA_URL: Option<Vec<String>> = None;
     pub static mut POST_JSON_DATA_BODY: Option<Vec<String>> = None;
+    pub static mut DELETE_URL: Option<Vec<String>> = None;
+    pub static mut COMPLETED_LECTURE_IDS: Option<Vec<LectureId>> = None;
     pub static mut PARSE: Option<Vec<String>> = None;
+    pub static mut WRITE_FILE_PATH: Option<Vec<String>> = None;
+    pub static mut RENAME_FILE_PATH: Option<Vec<String>> = None;
+    pub static mut REMOVE_FILE_PATH: Option<Vec<String>> = None;
+    pub static mut AVAILABLE_SPACE: Option<u64> = None;
+    /// Urls answering 500, as udemy would on an outage.
+    pub static mut FAILING_URLS: Option<Vec<String>> = None;
+
+    static MOCKS: Mutex<()> = Mutex::new(());
+
+    /// Give a test the mock state above for itself, cleared, until the guard is
+    /// dropped. Every test using the mocks has to hold it.
+    pub fn lock_mocks() -> MutexGuard<'static, ()> {
+        let guard = MOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            GETS_AS_JSON_URL = None;
+            GETS_CONTENT_LENGTH_URL = None;
+            GETS_AS_DATA_URL = None;
+            GETS_AS_DATA_EXPIRED = None;
+            POST_JSON_DATA_URL = None;
+            POST_JSON_DATA_BODY = None;
+            DELETE_URL = None;
+            COMPLETED_LECTURE_IDS = None;
+            PARSE = None;
+            WRITE_FILE_PATH = None;
+            RENAME_FILE_PATH = None;
+            REMOVE_FILE_PATH = None;
+            AVAILABLE_SPACE = None;
+            FAILING_URLS = None;
+        }
+        guard
+    }
 
     pub struct MockHttpClient {}
 
@@ -28,6 +67,16 @@ pub mod test {
                     }
                     None => panic!(),
                 }
+                if let Some(ref failing) = FAILING_URLS {
+                    if failing.iter().any(|f| f == url) {
+                        return Err(UdemyError::HttpStatus {
+                            url: url.into(),
+                            status: StatusCode::INTERNAL_SERVER_ERROR,
+                            detail: None,
+                        }
+                        .into());
+                    }
+                }
             };
             Ok(format!(r#"{{ "url": "{}" }}"#, url))
         }
@@ -43,7 +92,7 @@ pub mod test {
             };
             Ok(321)
         }
-        fn get_as_data(&self, url: &str, _f: &mut dyn FnMut(u64)) -> Result<Vec<u8>> {
+        fn get_as_data(&self, url: &str, buf: &mut Vec<u8>, _f: &mut dyn FnMut(u64)) -> Result<()> {
             println!("get_as_data url={}", url);
             unsafe {
                 match GETS_AS_DATA_URL {
@@ -52,8 +101,21 @@ pub mod test {
                     }
                     None => panic!(),
                 }
+                if let Some(ref mut expired) = GETS_AS_DATA_EXPIRED {
+                    if let Some(position) = expired.iter().position(|e| e == url) {
+                        expired.remove(position);
+                        buf.resize(100, 0);
+                        return Err(UdemyError::HttpStatus {
+                            url: url.into(),
+                            status: StatusCode::FORBIDDEN,
+                            detail: None,
+                        }
+                        .into());
+                    }
+                }
             };
-            Ok(vec![])
+            buf.resize(321, 0);
+            Ok(())
         }
         fn post_json(&self, url: &str, json: &Value, _auth: &Auth) -> Result<()> {
             unsafe {
@@ -73,6 +135,17 @@ pub mod test {
 
             Ok(())
         }
+        fn delete(&self, url: &str, _auth: &Auth) -> Result<()> {
+            unsafe {
+                match DELETE_URL {
+                    Some(ref mut du) => {
+                        du.push(String::from(url));
+                    }
+                    None => panic!(),
+                }
+            };
+            Ok(())
+        }
     }
 
     pub struct MockParser {}
@@ -97,6 +170,10 @@ pub mod test {
                 id: 54321,
                 url: "the-url".into(),
                 published_title: "css-the-complete-guide-incl-flexbox-grid-sass".into(),
+                title: "CSS - The Complete Guide".into(),
+                visible_instructors: vec![],
+                headline: String::new(),
+                image_url: None,
             }])
         }
         fn parse_course_content(&self, full_course: &Value) -> Result<CourseContent> {
@@ -112,8 +189,12 @@ pub mod test {
                 chapters: vec![Chapter {
                     object_index: 1,
                     title: "The Chapter".into(),
+                    description: "<p>What the chapter is about.</p>".into(),
                     lectures: vec![Lecture {
                         has_video: true,
+                        asset_type: "Video".into(),
+                        duration: 147,
+                        supplementary_assets: vec![],
                         filename: "blah-blah.mp4".into(),
                         id: 4321,
                         object_index: 1,
@@ -126,6 +207,7 @@ pub mod test {
             Ok(LectureDetail {
                 id: 4321,
                 title: "The lecture title".into(),
+                description: "<p>What the lecture is about.</p>".into(),
                 asset: Asset {
                     title: "native-app-03-creating-an-android-app.mp4".into(),
                     asset_type: "Video".into(),
@@ -135,9 +217,38 @@ pub mod test {
                         label: "720".into(),
                         file: "http://host-name/the-filename.mp4".into(),
                     }]),
+                    thumbnail_url: Some("http://host-name/the-thumbnail.jpg".into()),
+                    body: None,
+                    captions: vec![Caption {
+                        locale: "en_US".into(),
+                        label: "English".into(),
+                        url: "http://host-name/the-caption.vtt".into(),
+                        is_translation: false,
+                    }],
                 },
             })
         }
+        fn parse_user(&self, _user: &Value) -> Result<User> {
+            Ok(User {
+                id: 1234,
+                title: "John Doe".into(),
+            })
+        }
+        fn parse_supplementary_asset(&self, _asset: &Value) -> Result<Vec<DownloadUrl>> {
+            Ok(vec![DownloadUrl {
+                r#type: None,
+                label: "download".into(),
+                file: "http://host-name/exercise.zip".into(),
+            }])
+        }
+        fn parse_completed_lecture_ids(&self, _progress: &Value) -> Result<Vec<LectureId>> {
+            unsafe {
+                match COMPLETED_LECTURE_IDS {
+                    Some(ref ids) => Ok(ids.clone()),
+                    None => Ok(vec![]),
+                }
+            }
+        }
     }
 
     pub struct MockFsHelper {}
@@ -146,5 +257,52 @@ pub mod test {
         fn create_dir_recursive(&self, _path: &str) -> Result<()> {
             Ok(())
         }
+        fn write_file(&self, path: &str, _data: &[u8]) -> Result<()> {
+            unsafe {
+                if let Some(ref mut wf) = WRITE_FILE_PATH {
+                    wf.push(String::from(path));
+                }
+            };
+            Ok(())
+        }
+        fn read_file(&self, path: &str) -> Result<String> {
+            if path.ends_with("failures.json") {
+                Ok(r#"[{"course": "css-the-complete-guide-incl-flexbox-grid-sass", "chapter": 1, "lecture": 1, "lecture_id": 4321, "title": "The Lecture", "error": "boom"}]"#.into())
+            } else {
+                Ok("[]".into())
+            }
+        }
+        fn rename_file(&self, from: &str, to: &str) -> Result<()> {
+            unsafe {
+                if let Some(ref mut rf) = RENAME_FILE_PATH {
+                    rf.push(format!("{} -> {}", from, to));
+                }
+            };
+            Ok(())
+        }
+        fn create_new_file(&self, _path: &str, _data: &[u8]) -> Result<bool> {
+            Ok(true)
+        }
+        fn remove_file(&self, path: &str) -> Result<()> {
+            unsafe {
+                if let Some(ref mut rf) = REMOVE_FILE_PATH {
+                    rf.push(String::from(path));
+                }
+            };
+            Ok(())
+        }
+        fn file_exists(&self, path: &str) -> bool {
+            path.ends_with(".mp4")
+        }
+        fn list_dir(&self, _path: &str) -> Result<Vec<String>> {
+            Ok(vec![
+                "001 The Lecture.mp4".into(),
+                "001 The Lecture.en_US.vtt".into(),
+                "001 exercise.zip".into(),
+            ])
+        }
+        fn available_space(&self, _path: &str) -> Result<u64> {
+            Ok(unsafe { AVAILABLE_SPACE }.unwrap_or(u64::MAX))
+        }
     }
 }
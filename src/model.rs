@@ -3,7 +3,6 @@ use serde_derive::{Deserialize, Serialize};
 pub type CourseId = u64;
 pub type LectureId = u64;
 pub type ObjectIndex = u64;
-pub type VideoQuality = u64;
 
 /// Course information coming from subscribed courses.
 #[derive(Serialize, Deserialize, Debug)]
@@ -11,6 +10,21 @@ pub struct Course {
     pub id: CourseId,
     pub url: String,
     pub published_title: String,
+    /// Title shown to users, missing from recordings of older versions.
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub visible_instructors: Vec<Instructor>,
+    #[serde(default)]
+    pub headline: String,
+    #[serde(default, rename = "image_480x270")]
+    pub image_url: Option<String>,
+}
+
+/// Instructor of a course.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Instructor {
+    pub title: String,
 }
 
 /// Information on downloadable media.
@@ -28,6 +42,20 @@ pub struct Asset {
     pub asset_type: String,
     pub time_estimation: u64,
     pub download_urls: Option<Vec<DownloadUrl>>,
+    pub thumbnail_url: Option<String>,
+    /// Html content of articles.
+    pub body: Option<String>,
+    pub captions: Vec<Caption>,
+}
+
+/// Subtitles of a video, in WebVTT.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Caption {
+    pub locale: String,
+    pub label: String,
+    pub url: String,
+    /// Whether the caption was translated from another locale.
+    pub is_translation: bool,
 }
 
 /// Lecture information. Coming from lecture detail.
@@ -35,9 +63,19 @@ pub struct Asset {
 pub struct LectureDetail {
     pub id: LectureId,
     pub title: String,
+    /// Html description, empty when there is none.
+    pub description: String,
     pub asset: Asset,
 }
 
+/// Supplementary asset (resource) attached to a lecture.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SupplementaryAsset {
+    pub id: u64,
+    pub title: String,
+    pub asset_type: String,
+}
+
 /// Lecture information. Coming from genaral course information.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Lecture {
@@ -46,6 +84,10 @@ pub struct Lecture {
     pub title: String,
     pub filename: String,
     pub has_video: bool,
+    pub asset_type: String,
+    /// Length of the video in seconds, 0 when not a video.
+    pub duration: u64,
+    pub supplementary_assets: Vec<SupplementaryAsset>,
 }
 
 /// Chapter information.
@@ -53,6 +95,7 @@ pub struct Lecture {
 pub struct Chapter {
     pub object_index: ObjectIndex,
     pub title: String,
+    pub description: String,
     pub lectures: Vec<Lecture>,
 }
 
@@ -62,6 +105,13 @@ pub struct CourseContent {
     pub chapters: Vec<Chapter>,
 }
 
+/// Information on the authenticated user.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct User {
+    pub id: u64,
+    pub title: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuthResponse {
     pub _class: String,
@@ -75,15 +125,27 @@ pub struct CompleteRequest {
     pub downloaded: bool,
 }
 
+/// Cookie as exported by a browser in a cookies.txt file.
+#[derive(Debug, Clone)]
+pub struct Cookie {
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub expires: u64,
+    pub name: String,
+    pub value: String,
+}
+
 #[derive(Debug)]
 pub struct Auth {
     pub access_token: Option<String>,
+    pub cookies: Vec<Cookie>,
 }
 
 impl Auth {
     pub fn with_token(access_token: &str) -> Auth {
         Auth {
             access_token: Some(access_token.into()),
+            cookies: vec![],
         }
     }
 }
@@ -0,0 +1,738 @@
+use failure::format_err;
+
+use crate::result::Result;
+
+/// Tags written in the iTunes style metadata of an MP4 file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mp4Tags {
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub grouping: Option<String>,
+    pub artist: Option<String>,
+    /// Track number and total number of tracks.
+    pub track: Option<(u16, u16)>,
+    /// JPEG or PNG image.
+    pub cover: Option<Vec<u8>>,
+}
+
+/// Box of an MP4 file, with its raw bytes and its payload after the header.
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    raw: &'a [u8],
+    payload: &'a [u8],
+}
+
+/// Extract the audio track of an MP4 video into an `.m4a` file, copying the
+/// AAC samples as they are and writing the tags.
+pub fn extract_audio(data: &[u8], tags: &Mp4Tags) -> Result<Vec<u8>> {
+    let top = parse_boxes(data)?;
+    let moov = find_box(&top, b"moov")?;
+    let moov_boxes = parse_boxes(moov.payload)?;
+    let trak = moov_boxes
+        .iter()
+        .filter(|b| &b.kind == b"trak")
+        .find(|trak| handler_type(trak.payload).ok() == Some(*b"soun"))
+        .ok_or_else(|| format_err!("No audio track in MP4 file"))?;
+    let stbl = child(trak.payload, &[b"mdia", b"minf", b"stbl"])?;
+    let (samples, chunk_offsets) = read_chunks(data, stbl)?;
+
+    let tkhd = child(trak.payload, &[b"tkhd"])?;
+    let duration = if tkhd.first() == Some(&1) {
+        read_u64(tkhd, 28)?
+    } else {
+        u64::from(read_u32(tkhd, 20)?)
+    };
+    let mvhd = find_box(&moov_boxes, b"mvhd")?;
+    let mut mvhd_payload = mvhd.payload.to_vec();
+    if mvhd_payload.first() == Some(&1) {
+        write_at(&mut mvhd_payload, 24, &duration.to_be_bytes())?;
+    } else {
+        write_at(&mut mvhd_payload, 16, &(duration as u32).to_be_bytes())?;
+    }
+
+    let use_co64 = data.len() as u64 > u64::from(u32::MAX);
+    let build_moov = |base: u64| -> Result<Vec<u8>> {
+        let offsets: Vec<u64> = chunk_offsets.iter().map(|offset| base + offset).collect();
+        let trak = rebuild_track(trak.payload, &offsets, use_co64)?;
+        Ok(make_box(
+            b"moov",
+            &[
+                make_box(b"mvhd", &mvhd_payload),
+                trak,
+                make_udta(tags, None)?,
+            ]
+            .concat(),
+        ))
+    };
+    let ftyp = make_box(
+        b"ftyp",
+        &[&b"M4A "[..], &[0, 0, 0, 0], b"M4A ", b"mp42", b"isom"].concat(),
+    );
+    let mdat = make_box(b"mdat", &samples);
+    let mdat_header = mdat.len() - samples.len();
+    // Offsets do not change the size of the moov box, which is built twice to
+    // learn where the samples start.
+    let base = (ftyp.len() + build_moov(0)?.len() + mdat_header) as u64;
+    Ok([ftyp, build_moov(base)?, mdat].concat())
+}
+
+/// Write the tags of an MP4 file in place of the ones it has. The chunk
+/// offsets of the samples stored after the moov box are shifted when its size
+/// changes.
+pub fn write_tags(data: &[u8], tags: &Mp4Tags) -> Result<Vec<u8>> {
+    let top = parse_boxes(data)?;
+    let moov = find_box(&top, b"moov")?;
+    let moov_start = (moov.raw.as_ptr() as usize - data.as_ptr() as usize) as u64;
+    let build_moov = |shift: i64| -> Result<Vec<u8>> {
+        let shift_offsets = |b: &Mp4Box| -> Result<Vec<u8>> {
+            let wide = &b.kind == b"co64";
+            let width = if wide { 8 } else { 4 };
+            let count = read_u32(b.payload, 4)? as usize;
+            let mut payload = b.payload.to_vec();
+            for i in 0..count {
+                let pos = 8 + width * i;
+                let offset = if wide {
+                    read_u64(b.payload, pos)?
+                } else {
+                    u64::from(read_u32(b.payload, pos)?)
+                };
+                let offset = if offset > moov_start {
+                    (offset as i64 + shift) as u64
+                } else {
+                    offset
+                };
+                if wide {
+                    write_at(&mut payload, pos, &offset.to_be_bytes())?;
+                } else if offset > u64::from(u32::MAX) {
+                    return Err(format_err!("MP4 file too large to be tagged"));
+                } else {
+                    write_at(&mut payload, pos, &(offset as u32).to_be_bytes())?;
+                }
+            }
+            Ok(make_box(&b.kind, &payload))
+        };
+        let mut payload = Vec::with_capacity(moov.payload.len());
+        let mut udta = None;
+        for b in parse_boxes(moov.payload)? {
+            match &b.kind {
+                b"trak" => payload.extend(map_track(b.payload, &[], &shift_offsets)?),
+                b"udta" => udta = Some(b),
+                _ => payload.extend_from_slice(b.raw),
+            }
+        }
+        payload.extend(make_udta(tags, udta.as_ref())?);
+        Ok(make_box(b"moov", &payload))
+    };
+    let shift = build_moov(0)?.len() as i64 - moov.raw.len() as i64;
+    let moov_data = build_moov(shift)?;
+    Ok(top
+        .iter()
+        .flat_map(|b| {
+            if &b.kind == b"moov" {
+                moov_data.as_slice()
+            } else {
+                b.raw
+            }
+        })
+        .cloned()
+        .collect())
+}
+
+/// Copy the chunks of a track, telling the offset of each one in the copy.
+fn read_chunks(data: &[u8], stbl: &[u8]) -> Result<(Vec<u8>, Vec<u64>)> {
+    let stbl_boxes = parse_boxes(stbl)?;
+
+    let stsz = find_box(&stbl_boxes, b"stsz")?.payload;
+    let sample_size = read_u32(stsz, 4)?;
+    let sample_count = read_u32(stsz, 8)? as usize;
+    // A forged count is not to be allocated before the samples are read.
+    let fits = if sample_size != 0 {
+        (sample_count as u64)
+            .checked_mul(u64::from(sample_size))
+            .is_some_and(|total| total <= data.len() as u64)
+    } else {
+        stsz.len() as u64 >= 12 + 4 * sample_count as u64
+    };
+    if !fits {
+        return Err(format_err!("Invalid MP4 sample count {}", sample_count));
+    }
+    let sizes = if sample_size != 0 {
+        vec![sample_size; sample_count]
+    } else {
+        (0..sample_count)
+            .map(|i| read_u32(stsz, 12 + 4 * i))
+            .collect::<Result<Vec<u32>>>()?
+    };
+
+    let offsets = match find_box(&stbl_boxes, b"stco") {
+        Ok(stco) => (0..read_u32(stco.payload, 4)? as usize)
+            .map(|i| read_u32(stco.payload, 8 + 4 * i).map(u64::from))
+            .collect::<Result<Vec<u64>>>()?,
+        Err(_) => {
+            let co64 = find_box(&stbl_boxes, b"co64")?.payload;
+            (0..read_u32(co64, 4)? as usize)
+                .map(|i| read_u64(co64, 8 + 8 * i))
+                .collect::<Result<Vec<u64>>>()?
+        }
+    };
+
+    let stsc = find_box(&stbl_boxes, b"stsc")?.payload;
+    let runs = (0..read_u32(stsc, 4)? as usize)
+        .map(|i| Ok((read_u32(stsc, 8 + 12 * i)?, read_u32(stsc, 12 + 12 * i)?)))
+        .collect::<Result<Vec<(u32, u32)>>>()?;
+
+    let mut samples = Vec::new();
+    let mut chunk_offsets = Vec::with_capacity(offsets.len());
+    let mut sample = 0;
+    for (index, offset) in offsets.iter().enumerate() {
+        let chunk = index as u32 + 1;
+        let count = runs
+            .iter()
+            .rev()
+            .find(|(first_chunk, _)| *first_chunk <= chunk)
+            .map(|(_, count)| *count as usize)
+            .unwrap_or(0);
+        let length: u64 = sizes
+            .get(sample..sample + count)
+            .ok_or_else(|| format_err!("Inconsistent MP4 sample table"))?
+            .iter()
+            .map(|size| u64::from(*size))
+            .sum();
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| data.get(*offset as usize..end as usize))
+            .ok_or_else(|| format_err!("MP4 chunk out of the file at {}", offset))?;
+        chunk_offsets.push(samples.len() as u64);
+        samples.extend_from_slice(bytes);
+        sample += count;
+    }
+    Ok((samples, chunk_offsets))
+}
+
+/// Copy a track, dropping references to other tracks and pointing its chunk
+/// offsets to their new place.
+fn rebuild_track(trak: &[u8], offsets: &[u64], use_co64: bool) -> Result<Vec<u8>> {
+    let chunk_offsets = if use_co64 {
+        let entries: Vec<u8> = offsets.iter().flat_map(|o| o.to_be_bytes()).collect();
+        make_full_box(
+            b"co64",
+            &[&(offsets.len() as u32).to_be_bytes()[..], &entries].concat(),
+        )
+    } else {
+        let entries: Vec<u8> = offsets
+            .iter()
+            .flat_map(|o| (*o as u32).to_be_bytes())
+            .collect();
+        make_full_box(
+            b"stco",
+            &[&(offsets.len() as u32).to_be_bytes()[..], &entries].concat(),
+        )
+    };
+    map_track(trak, &[b"tref", b"udta", b"meta"], &|_| {
+        Ok(chunk_offsets.clone())
+    })
+}
+
+/// Copy a track without some of its children, its chunk offsets box being
+/// replaced.
+fn map_track(
+    trak: &[u8],
+    dropped: &[&[u8; 4]],
+    chunk_offsets: &dyn Fn(&Mp4Box) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let stbl = |stbl: &Mp4Box| {
+        map_children(stbl, &mut |b| match &b.kind {
+            b"stco" | b"co64" => chunk_offsets(b).map(Some),
+            _ => Ok(Some(b.raw.to_vec())),
+        })
+    };
+    let minf = |minf: &Mp4Box| {
+        map_children(minf, &mut |b| match &b.kind {
+            b"stbl" => stbl(b).map(Some),
+            _ => Ok(Some(b.raw.to_vec())),
+        })
+    };
+    let mdia = |mdia: &Mp4Box| {
+        map_children(mdia, &mut |b| match &b.kind {
+            b"minf" => minf(b).map(Some),
+            _ => Ok(Some(b.raw.to_vec())),
+        })
+    };
+    let trak = Mp4Box {
+        kind: *b"trak",
+        raw: trak,
+        payload: trak,
+    };
+    map_children(&trak, &mut |b| match &b.kind {
+        b"mdia" => mdia(b).map(Some),
+        kind if dropped.contains(&kind) => Ok(None),
+        _ => Ok(Some(b.raw.to_vec())),
+    })
+}
+
+/// Build a container box out of the children of another one, each child being
+/// kept, replaced or dropped.
+fn map_children(
+    container: &Mp4Box,
+    f: &mut dyn FnMut(&Mp4Box) -> Result<Option<Vec<u8>>>,
+) -> Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(container.payload.len());
+    for b in parse_boxes(container.payload)? {
+        if let Some(bytes) = f(&b)? {
+            payload.extend(bytes);
+        }
+    }
+    Ok(make_box(&container.kind, &payload))
+}
+
+/// Build the user data box holding the tags, keeping what an existing one
+/// holds besides metadata.
+fn make_udta(tags: &Mp4Tags, existing: Option<&Mp4Box>) -> Result<Vec<u8>> {
+    let mut items: Vec<u8> = [
+        (b"\xa9nam", &tags.title),
+        (b"\xa9alb", &tags.album),
+        (b"\xa9grp", &tags.grouping),
+        (b"\xa9ART", &tags.artist),
+    ]
+    .iter()
+    .filter_map(|(kind, value)| value.as_ref().map(|value| text_item(kind, value)))
+    .flatten()
+    .collect();
+    if let Some((track, total)) = tags.track {
+        let value = [
+            &[0, 0][..],
+            &track.to_be_bytes(),
+            &total.to_be_bytes(),
+            &[0, 0],
+        ]
+        .concat();
+        items.extend(data_item(b"trkn", 0, &value));
+    }
+    if let Some(cover) = &tags.cover {
+        // Well-known types 13 and 14 are JPEG and PNG images.
+        let data_type = if cover.starts_with(b"\x89PNG") {
+            14
+        } else {
+            13
+        };
+        items.extend(data_item(b"covr", data_type, cover));
+    }
+    let hdlr = make_full_box(
+        b"hdlr",
+        &[&[0, 0, 0, 0][..], b"mdir", b"appl", &[0; 9]].concat(),
+    );
+    let meta = make_full_box(b"meta", &[hdlr, make_box(b"ilst", &items)].concat());
+    let mut payload = Vec::new();
+    if let Some(existing) = existing {
+        for b in parse_boxes(existing.payload)? {
+            if &b.kind != b"meta" {
+                payload.extend_from_slice(b.raw);
+            }
+        }
+    }
+    payload.extend(meta);
+    Ok(make_box(b"udta", &payload))
+}
+
+fn text_item(kind: &[u8; 4], value: &str) -> Vec<u8> {
+    // Well-known type 1 is UTF-8 text.
+    data_item(kind, 1, value.as_bytes())
+}
+
+fn data_item(kind: &[u8; 4], data_type: u32, value: &[u8]) -> Vec<u8> {
+    // The type is followed by an empty locale.
+    let data = make_box(
+        b"data",
+        &[&data_type.to_be_bytes()[..], &[0, 0, 0, 0], value].concat(),
+    );
+    make_box(kind, &data)
+}
+
+/// Handler of a track, such as `soun` or `vide`.
+fn handler_type(trak: &[u8]) -> Result<[u8; 4]> {
+    let hdlr = child(trak, &[b"mdia", b"hdlr"])?;
+    let mut kind = [0; 4];
+    kind.copy_from_slice(
+        hdlr.get(8..12)
+            .ok_or_else(|| format_err!("Truncated MP4 box hdlr"))?,
+    );
+    Ok(kind)
+}
+
+fn parse_boxes(data: &[u8]) -> Result<Vec<Mp4Box<'_>>> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let size = read_u32(data, pos)?;
+        let mut kind = [0; 4];
+        kind.copy_from_slice(
+            data.get(pos + 4..pos + 8)
+                .ok_or_else(|| format_err!("Truncated MP4 box at {}", pos))?,
+        );
+        let (header, size) = match size {
+            0 => (8, (data.len() - pos) as u64),
+            1 => (16, read_u64(data, pos + 8)?),
+            size => (8, u64::from(size)),
+        };
+        let fits = (pos as u64)
+            .checked_add(size)
+            .is_some_and(|end| end <= data.len() as u64);
+        if size < header || !fits {
+            return Err(format_err!(
+                "Invalid size of MP4 box {}",
+                String::from_utf8_lossy(&kind)
+            ));
+        }
+        let end = pos + size as usize;
+        boxes.push(Mp4Box {
+            kind,
+            raw: &data[pos..end],
+            payload: &data[pos + header as usize..end],
+        });
+        pos = end;
+    }
+    Ok(boxes)
+}
+
+fn find_box<'b, 'a>(boxes: &'b [Mp4Box<'a>], kind: &[u8; 4]) -> Result<&'b Mp4Box<'a>> {
+    boxes
+        .iter()
+        .find(|b| &b.kind == kind)
+        .ok_or_else(|| format_err!("Missing MP4 box {}", String::from_utf8_lossy(kind)))
+}
+
+/// Payload of a box nested in containers, following the kinds of a path.
+fn child<'a>(payload: &'a [u8], path: &[&[u8; 4]]) -> Result<&'a [u8]> {
+    let mut payload = payload;
+    for kind in path {
+        payload = find_box(&parse_boxes(payload)?, kind)?.payload;
+    }
+    Ok(payload)
+}
+
+fn make_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 16);
+    if payload.len() as u64 + 8 > u64::from(u32::MAX) {
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(kind);
+        data.extend_from_slice(&(payload.len() as u64 + 16).to_be_bytes());
+    } else {
+        data.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+        data.extend_from_slice(kind);
+    }
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Build a box with a version and flags of zero.
+fn make_full_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    make_box(kind, &[&[0, 0, 0, 0][..], payload].concat())
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(
+        data.get(pos..pos + 4)
+            .ok_or_else(|| format_err!("Truncated MP4 data at {}", pos))?,
+    );
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(data: &[u8], pos: usize) -> Result<u64> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(
+        data.get(pos..pos + 8)
+            .ok_or_else(|| format_err!("Truncated MP4 data at {}", pos))?,
+    );
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn write_at(data: &mut [u8], pos: usize, bytes: &[u8]) -> Result<()> {
+    data.get_mut(pos..pos + bytes.len())
+        .ok_or_else(|| format_err!("Truncated MP4 data at {}", pos))?
+        .copy_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Track whose samples are split in chunks, as (offset, sample sizes).
+    fn make_trak(handler: &[u8; 4], duration: u32, chunks: &[(u32, &[u32])]) -> Vec<u8> {
+        let mut tkhd = vec![0; 84];
+        tkhd[20..24].copy_from_slice(&duration.to_be_bytes());
+        let hdlr = [&[0; 4][..], handler, &[0; 13]].concat();
+        let stsc: Vec<u8> = chunks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (_, sizes))| {
+                [i as u32 + 1, sizes.len() as u32, 1]
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+        let sizes: Vec<u32> = chunks
+            .iter()
+            .flat_map(|(_, sizes)| sizes.to_vec())
+            .collect();
+        let stsz: Vec<u8> = [0, sizes.len() as u32]
+            .iter()
+            .chain(sizes.iter())
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let stco: Vec<u8> = std::iter::once(chunks.len() as u32)
+            .chain(chunks.iter().map(|(offset, _)| *offset))
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let stbl = [
+            make_full_box(b"stsd", &[0, 0, 0, 0]),
+            make_full_box(b"stts", &[0, 0, 0, 0]),
+            make_full_box(
+                b"stsc",
+                &[&(chunks.len() as u32).to_be_bytes()[..], &stsc].concat(),
+            ),
+            make_full_box(b"stsz", &stsz),
+            make_full_box(b"stco", &stco),
+        ]
+        .concat();
+        let minf = [make_full_box(b"smhd", &[0; 4]), make_box(b"stbl", &stbl)].concat();
+        let mdia = [
+            make_full_box(b"mdhd", &[0; 20]),
+            make_full_box(b"hdlr", &hdlr),
+            make_box(b"minf", &minf),
+        ]
+        .concat();
+        make_box(
+            b"trak",
+            &[make_box(b"tkhd", &tkhd), make_box(b"mdia", &mdia)].concat(),
+        )
+    }
+
+    /// Video with interleaved video and audio chunks, the moov box being before
+    /// the samples when fast starting.
+    fn make_video(fast_start: bool) -> Vec<u8> {
+        let ftyp = make_box(b"ftyp", b"isom\0\0\0\0isom");
+        let mdat = make_box(b"mdat", b"VVVVaabbbVVVVcccc");
+        let make_moov = |base: u32| {
+            make_box(
+                b"moov",
+                &[
+                    make_box(b"mvhd", &[0; 100]),
+                    make_trak(b"vide", 2000, &[(base, &[4]), (base + 9, &[4])]),
+                    make_trak(b"soun", 1990, &[(base + 4, &[2, 3]), (base + 13, &[4])]),
+                ]
+                .concat(),
+            )
+        };
+        if fast_start {
+            let base = (ftyp.len() + make_moov(0).len()) as u32 + 8;
+            [ftyp, make_moov(base), mdat].concat()
+        } else {
+            let base = ftyp.len() as u32 + 8;
+            [ftyp, mdat, make_moov(base)].concat()
+        }
+    }
+
+    /// Data of an item of the tags.
+    fn tag<'a>(moov: &'a [u8], kind: &[u8; 4]) -> &'a [u8] {
+        let meta = child(moov, &[b"udta", b"meta"]).unwrap();
+        &child(&meta[4..], &[b"ilst", kind, b"data"]).unwrap()[8..]
+    }
+
+    /// Samples of the chunks of the track of a handler.
+    fn chunks(data: &[u8], handler: &[u8; 4]) -> Vec<Vec<u8>> {
+        let moov = find_box(&parse_boxes(data).unwrap(), b"moov")
+            .unwrap()
+            .payload;
+        let trak = parse_boxes(moov)
+            .unwrap()
+            .into_iter()
+            .find(|b| &b.kind == b"trak" && &handler_type(b.payload).unwrap() == handler)
+            .unwrap()
+            .payload;
+        let stbl = child(trak, &[b"mdia", b"minf", b"stbl"]).unwrap();
+        let (samples, offsets) = read_chunks(data, stbl).unwrap();
+        offsets
+            .iter()
+            .zip(offsets.iter().skip(1).chain(Some(&(samples.len() as u64))))
+            .map(|(start, end)| samples[*start as usize..*end as usize].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn extract_audio_track() {
+        let tags = Mp4Tags {
+            title: Some("Introduction".into()),
+            grouping: Some("Getting Started".into()),
+            ..Mp4Tags::default()
+        };
+
+        let m4a = extract_audio(&make_video(false), &tags).unwrap();
+
+        let top = parse_boxes(&m4a).unwrap();
+        let kinds: Vec<&[u8; 4]> = top.iter().map(|b| &b.kind).collect();
+        assert_eq!(kinds, vec![b"ftyp", b"moov", b"mdat"]);
+        assert_eq!(top[2].payload, b"aabbbcccc");
+        let moov = top[1].payload;
+        let traks: Vec<Mp4Box> = parse_boxes(moov)
+            .unwrap()
+            .into_iter()
+            .filter(|b| &b.kind == b"trak")
+            .collect();
+        assert_eq!(traks.len(), 1);
+        let stco = child(moov, &[b"trak", b"mdia", b"minf", b"stbl", b"stco"]).unwrap();
+        let first = read_u32(stco, 8).unwrap() as usize;
+        let second = read_u32(stco, 12).unwrap() as usize;
+        assert_eq!(&m4a[first..first + 5], b"aabbb");
+        assert_eq!(&m4a[second..second + 4], b"cccc");
+        assert_eq!(
+            read_u32(child(moov, &[b"mvhd"]).unwrap(), 16).unwrap(),
+            1990
+        );
+        let ilst = child(moov, &[b"udta"]).unwrap();
+        let ilst = child(&parse_boxes(ilst).unwrap()[0].payload[4..], &[b"ilst"]).unwrap();
+        let title = child(ilst, &[b"\xa9nam", b"data"]).unwrap();
+        assert_eq!(&title[8..], b"Introduction");
+        let grouping = child(ilst, &[b"\xa9grp", b"data"]).unwrap();
+        assert_eq!(&grouping[8..], b"Getting Started");
+    }
+
+    #[test]
+    fn extract_audio_without_audio_track() {
+        let ftyp = make_box(b"ftyp", b"isom\0\0\0\0isom");
+        let moov = make_box(
+            b"moov",
+            &[
+                make_box(b"mvhd", &[0; 100]),
+                make_trak(b"vide", 2000, &[(16, &[4])]),
+            ]
+            .concat(),
+        );
+
+        let actual = extract_audio(
+            &[ftyp, make_box(b"mdat", b"VVVV"), moov].concat(),
+            &Mp4Tags::default(),
+        );
+
+        assert!(actual.is_err());
+        assert!(extract_audio(b"not an mp4", &Mp4Tags::default()).is_err());
+    }
+
+    #[test]
+    fn read_chunks_with_forged_sample_count() {
+        let stbl = make_full_box(
+            b"stsz",
+            &[&1u32.to_be_bytes()[..], &u32::MAX.to_be_bytes()].concat(),
+        );
+
+        assert!(read_chunks(b"VVVV", &stbl).is_err());
+    }
+
+    #[test]
+    fn read_chunks_with_chunk_past_end() {
+        let stbl = [
+            make_full_box(
+                b"stsc",
+                &[1, 1, 1, 1]
+                    .iter()
+                    .flat_map(|v: &u32| v.to_be_bytes())
+                    .collect::<Vec<u8>>(),
+            ),
+            make_full_box(
+                b"stsz",
+                &[0u32, 1, 4]
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect::<Vec<u8>>(),
+            ),
+            make_full_box(
+                b"co64",
+                &[&1u32.to_be_bytes()[..], &(u64::MAX - 1).to_be_bytes()].concat(),
+            ),
+        ]
+        .concat();
+
+        assert!(read_chunks(b"VVVV", &stbl).is_err());
+    }
+
+    #[test]
+    fn parse_boxes_with_forged_largesize() {
+        let forged = [
+            &make_box(b"free", &[])[..],
+            &[0, 0, 0, 1],
+            b"free",
+            &u64::MAX.to_be_bytes(),
+        ]
+        .concat();
+
+        assert!(parse_boxes(&forged).is_err());
+    }
+
+    #[test]
+    fn write_tags_before_samples() {
+        let tags = Mp4Tags {
+            title: Some("Introduction".into()),
+            album: Some("CSS - The Complete Guide".into()),
+            grouping: Some("Getting Started".into()),
+            artist: Some("Maximilian".into()),
+            track: Some((3, 120)),
+            cover: Some(b"\x89PNG the image".to_vec()),
+        };
+
+        let tagged = write_tags(&make_video(true), &tags).unwrap();
+
+        let top = parse_boxes(&tagged).unwrap();
+        let kinds: Vec<&[u8; 4]> = top.iter().map(|b| &b.kind).collect();
+        assert_eq!(kinds, vec![b"ftyp", b"moov", b"mdat"]);
+        assert_eq!(
+            chunks(&tagged, b"vide"),
+            vec![b"VVVV".to_vec(), b"VVVV".to_vec()]
+        );
+        assert_eq!(
+            chunks(&tagged, b"soun"),
+            vec![b"aabbb".to_vec(), b"cccc".to_vec()]
+        );
+        let moov = top[1].payload;
+        assert_eq!(tag(moov, b"\xa9alb"), b"CSS - The Complete Guide");
+        assert_eq!(tag(moov, b"\xa9ART"), b"Maximilian");
+        assert_eq!(tag(moov, b"trkn"), &[0, 0, 0, 3, 0, 120, 0, 0]);
+        assert_eq!(tag(moov, b"covr"), b"\x89PNG the image");
+        let covr = child(
+            &child(moov, &[b"udta", b"meta"]).unwrap()[4..],
+            &[b"ilst", b"covr", b"data"],
+        )
+        .unwrap();
+        assert_eq!(read_u32(covr, 0).unwrap(), 14);
+    }
+
+    #[test]
+    fn write_tags_again() {
+        let first = Mp4Tags {
+            title: Some("First".into()),
+            ..Mp4Tags::default()
+        };
+        let second = Mp4Tags {
+            title: Some("Second".into()),
+            ..Mp4Tags::default()
+        };
+
+        let tagged = write_tags(&make_video(false), &first).unwrap();
+        let tagged = write_tags(&tagged, &second).unwrap();
+
+        let top = parse_boxes(&tagged).unwrap();
+        let moov = top[2].payload;
+        let udtas = parse_boxes(moov)
+            .unwrap()
+            .into_iter()
+            .filter(|b| &b.kind == b"udta")
+            .count();
+        assert_eq!(udtas, 1);
+        assert_eq!(tag(moov, b"\xa9nam"), b"Second");
+        assert_eq!(
+            chunks(&tagged, b"soun"),
+            vec![b"aabbb".to_vec(), b"cccc".to_vec()]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::model::*;
+use crate::utils::escape;
+
+/// Name of the show metadata file in the course directory.
+pub const TVSHOW_NFO: &str = "tvshow.nfo";
+/// Name of the season metadata file in each chapter directory.
+pub const SEASON_NFO: &str = "season.nfo";
+/// Name of the show poster in the course directory.
+pub const POSTER: &str = "poster.jpg";
+
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n";
+
+/// Show metadata of a course, as read by Jellyfin and Kodi.
+pub fn tvshow_nfo(course: &Course, course_name: &str) -> String {
+    let title = if course.title.is_empty() {
+        course_name
+    } else {
+        course.title.as_str()
+    };
+    let mut nfo = format!(
+        "{}<tvshow>\n  <title>{}</title>\n  <plot>{}</plot>\n  <studio>Udemy</studio>\n",
+        XML_HEADER,
+        escape(title),
+        escape(course.headline.as_str())
+    );
+    for instructor in course.visible_instructors.iter() {
+        nfo.push_str(
+            format!(
+                "  <actor>\n    <name>{}</name>\n    <role>Instructor</role>\n  </actor>\n",
+                escape(instructor.title.as_str())
+            )
+            .as_str(),
+        );
+    }
+    nfo.push_str(
+        format!(
+            "  <uniqueid type=\"udemy\" default=\"true\">{}</uniqueid>\n</tvshow>\n",
+            course.id
+        )
+        .as_str(),
+    );
+    nfo
+}
+
+/// Season metadata of a chapter.
+pub fn season_nfo(chapter: &Chapter) -> String {
+    format!(
+        "{}<season>\n  <title>{}</title>\n  <seasonnumber>{}</seasonnumber>\n  <plot>{}</plot>\n</season>\n",
+        XML_HEADER,
+        escape(chapter.title.as_str()),
+        chapter.object_index,
+        escape(strip_html(chapter.description.as_str()).as_str())
+    )
+}
+
+/// Episode metadata of a lecture, its description being html.
+pub fn episode_nfo(chapter: &Chapter, lecture: &Lecture, description: &str) -> String {
+    format!(
+        "{}<episodedetails>\n  <title>{}</title>\n  <season>{}</season>\n  <episode>{}</episode>\n  <plot>{}</plot>\n  <runtime>{}</runtime>\n  <fileinfo>\n    <streamdetails>\n      <video>\n        <durationinseconds>{}</durationinseconds>\n      </video>\n    </streamdetails>\n  </fileinfo>\n  <uniqueid type=\"udemy\" default=\"true\">{}</uniqueid>\n</episodedetails>\n",
+        XML_HEADER,
+        escape(lecture.title.as_str()),
+        chapter.object_index,
+        lecture.object_index,
+        escape(strip_html(description).as_str()),
+        lecture.duration.div_ceil(60),
+        lecture.duration,
+        lecture.id
+    )
+}
+
+/// Plain text of an html description, paragraphs and line breaks becoming new
+/// lines.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut tag: Option<String> = None;
+    for c in html.chars() {
+        match (c, tag.as_mut()) {
+            ('<', None) => tag = Some(String::new()),
+            ('>', Some(name)) => {
+                let name = name.to_lowercase();
+                if (name == "/p" || name.starts_with("br")) && !text.is_empty() {
+                    text.push('\n');
+                }
+                tag = None;
+            }
+            (c, Some(name)) => name.push(c),
+            (c, None) => text.push(c),
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_data::test::*;
+
+    #[test]
+    fn make_tvshow_nfo() {
+        let actual = tvshow_nfo(
+            &make_course(),
+            "css-the-complete-guide-incl-flexbox-grid-sass",
+        );
+
+        assert!(actual.starts_with("<?xml"));
+        assert!(actual.contains("<title>CSS - The Complete Guide</title>"));
+        assert!(actual.contains("<plot>Learn CSS from the ground up</plot>"));
+        assert!(actual.contains("<name>Maximilian Schwarzmüller</name>"));
+        assert!(actual.contains("<uniqueid type=\"udemy\" default=\"true\">54321</uniqueid>"));
+    }
+
+    #[test]
+    fn make_season_and_episode_nfo() {
+        let course_content = make_test_course_content();
+        let chapter = &course_content.chapters[0];
+
+        let season = season_nfo(chapter);
+        let episode = episode_nfo(
+            chapter,
+            &chapter.lectures[0],
+            "<p>Flexbox &amp; <b>grid</b></p><p>Part 2</p>",
+        );
+
+        assert!(season.contains("<seasonnumber>1</seasonnumber>"));
+        assert!(season.contains("<plot>What the chapter is about.</plot>"));
+        assert!(episode.contains("<episode>1</episode>"));
+        assert!(episode.contains("<plot>Flexbox &amp; grid\nPart 2</plot>"));
+        assert!(episode.contains("<runtime>3</runtime>"));
+        assert!(episode.contains("<durationinseconds>147</durationinseconds>"));
+    }
+}
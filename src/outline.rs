@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use failure::format_err;
+use serde_derive::Serialize;
+
+use crate::model::*;
+use crate::result::Result;
+use crate::utils::format_duration;
+
+/// Version of the outline schema, to be increased on any incompatible change.
+pub const OUTLINE_SCHEMA_VERSION: u32 = 1;
+
+/// Output formats supported by the info command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+    Csv,
+    Markdown,
+}
+
+pub const OUTPUT_FORMATS: &[&str] = &["text", "json", "yaml", "csv", "markdown"];
+
+impl FromStr for OutputFormat {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(format_err!("Unknown output format <{}>", s)),
+        }
+    }
+}
+
+/// Outline of a course, as exposed to other tools. Fields are only ever added
+/// within a schema version, see the README for their description.
+#[derive(Serialize, Debug)]
+pub struct Outline<'a> {
+    pub schema_version: u32,
+    pub course: OutlineCourse<'a>,
+    pub chapter_count: usize,
+    pub lecture_count: usize,
+    pub completed_lecture_count: Option<usize>,
+    pub duration: u64,
+    pub remaining_duration: Option<u64>,
+    pub chapters: Vec<OutlineChapter<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OutlineCourse<'a> {
+    pub id: CourseId,
+    pub url: &'a str,
+    pub published_title: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OutlineChapter<'a> {
+    pub object_index: ObjectIndex,
+    pub title: &'a str,
+    pub duration: u64,
+    pub lectures: Vec<OutlineLecture<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OutlineLecture<'a> {
+    pub id: LectureId,
+    pub object_index: ObjectIndex,
+    pub title: &'a str,
+    pub asset_type: &'a str,
+    pub duration: u64,
+    pub completed: Option<bool>,
+    pub supplementary_assets: &'a [SupplementaryAsset],
+}
+
+/// One line per lecture, for csv output.
+#[derive(Serialize)]
+struct OutlineRow<'a> {
+    chapter_index: ObjectIndex,
+    chapter_title: &'a str,
+    lecture_id: LectureId,
+    lecture_index: ObjectIndex,
+    title: &'a str,
+    asset_type: &'a str,
+    duration: u64,
+    supplementary_assets: usize,
+    completed: Option<bool>,
+}
+
+impl<'a> Outline<'a> {
+    /// Outline of a course, the completion of its lectures being unknown
+    /// without `completed`.
+    pub fn new(
+        course: &'a Course,
+        course_content: &'a CourseContent,
+        completed: Option<&HashSet<LectureId>>,
+    ) -> Outline<'a> {
+        let chapters: Vec<OutlineChapter> = course_content
+            .chapters
+            .iter()
+            .map(|chapter| OutlineChapter {
+                object_index: chapter.object_index,
+                title: chapter.title.as_str(),
+                duration: chapter
+                    .lectures
+                    .iter()
+                    .map(|lecture| lecture.duration)
+                    .sum(),
+                lectures: chapter
+                    .lectures
+                    .iter()
+                    .map(|lecture| OutlineLecture {
+                        id: lecture.id,
+                        object_index: lecture.object_index,
+                        title: lecture.title.as_str(),
+                        asset_type: lecture.asset_type.as_str(),
+                        duration: lecture.duration,
+                        completed: completed.map(|completed| completed.contains(&lecture.id)),
+                        supplementary_assets: &lecture.supplementary_assets,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let lectures = || chapters.iter().flat_map(|chapter| chapter.lectures.iter());
+        let completed_lecture_count = completed.map(|_| {
+            lectures()
+                .filter(|lecture| lecture.completed == Some(true))
+                .count()
+        });
+        let remaining_duration = completed.map(|_| {
+            lectures()
+                .filter(|lecture| lecture.completed == Some(false))
+                .map(|lecture| lecture.duration)
+                .sum()
+        });
+        Outline {
+            schema_version: OUTLINE_SCHEMA_VERSION,
+            course: OutlineCourse {
+                id: course.id,
+                url: course.url.as_str(),
+                published_title: course.published_title.as_str(),
+            },
+            chapter_count: chapters.len(),
+            lecture_count: chapters.iter().map(|chapter| chapter.lectures.len()).sum(),
+            completed_lecture_count,
+            duration: chapters.iter().map(|chapter| chapter.duration).sum(),
+            remaining_duration,
+            chapters,
+        }
+    }
+
+    /// Render the outline in a structured format, text is handled by the info command.
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            OutputFormat::Yaml => Ok(serde_yaml::to_string(self)?),
+            OutputFormat::Csv => self.to_csv(),
+            OutputFormat::Markdown => Ok(self.to_markdown()),
+            OutputFormat::Text => Err(format_err!("Text output is not rendered from an outline")),
+        }
+    }
+
+    fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for chapter in self.chapters.iter() {
+            for lecture in chapter.lectures.iter() {
+                writer.serialize(OutlineRow {
+                    chapter_index: chapter.object_index,
+                    chapter_title: chapter.title,
+                    lecture_id: lecture.id,
+                    lecture_index: lecture.object_index,
+                    title: lecture.title,
+                    asset_type: lecture.asset_type,
+                    duration: lecture.duration,
+                    supplementary_assets: lecture.supplementary_assets.len(),
+                    completed: lecture.completed,
+                })?;
+            }
+        }
+        let data = writer
+            .into_inner()
+            .map_err(|e| format_err!("Could not write csv: {}", e))?;
+        Ok(String::from_utf8(data)?)
+    }
+
+    fn to_markdown(&self) -> String {
+        let progress = match (self.completed_lecture_count, self.remaining_duration) {
+            (Some(completed), Some(remaining)) => format!(
+                "{} completed, {} remaining",
+                completed,
+                format_duration(remaining)
+            ),
+            _ => "progress unknown".into(),
+        };
+        let mut markdown = format!(
+            "# {}\n\n{} chapters, {} lectures, {} ({})\n",
+            self.course.published_title,
+            self.chapter_count,
+            self.lecture_count,
+            format_duration(self.duration),
+            progress
+        );
+        for chapter in self.chapters.iter() {
+            markdown.push_str(
+                format!(
+                    "\n## {:03} {} ({})\n\n",
+                    chapter.object_index,
+                    chapter.title,
+                    format_duration(chapter.duration)
+                )
+                .as_str(),
+            );
+            for lecture in chapter.lectures.iter() {
+                markdown.push_str(
+                    format!(
+                        "- [{}] {:03} {} *{}{}*\n",
+                        match lecture.completed {
+                            Some(true) => "x",
+                            Some(false) => " ",
+                            None => "?",
+                        },
+                        lecture.object_index,
+                        lecture.title,
+                        lecture.asset_type,
+                        if lecture.duration > 0 {
+                            format!(" {}", format_duration(lecture.duration))
+                        } else {
+                            String::new()
+                        }
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::Value;
+
+    use crate::test_data::test::*;
+
+    #[test]
+    fn parse_output_format() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!(
+            "Markdown".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Markdown
+        );
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn render_json() {
+        let course = make_course();
+        let course_content = make_test_course_content();
+        let outline = Outline::new(
+            &course,
+            &course_content,
+            Some(&vec![4321].into_iter().collect()),
+        );
+
+        let actual = outline.render(OutputFormat::Json);
+
+        assert!(actual.is_ok());
+        let actual: Value = serde_json::from_str(actual.unwrap().as_str()).unwrap();
+        assert_eq!(actual["schema_version"], 1);
+        assert_eq!(actual["course"]["id"], 54321);
+        assert_eq!(actual["lecture_count"], 1);
+        assert_eq!(actual["duration"], 147);
+        assert_eq!(actual["completed_lecture_count"], 1);
+        assert_eq!(actual["remaining_duration"], 0);
+        assert_eq!(actual["chapters"][0]["object_index"], 1);
+        assert_eq!(actual["chapters"][0]["lectures"][0]["id"], 4321);
+        assert_eq!(actual["chapters"][0]["lectures"][0]["asset_type"], "Video");
+        assert_eq!(actual["chapters"][0]["lectures"][0]["completed"], true);
+    }
+
+    #[test]
+    fn render_yaml() {
+        let course = make_course();
+        let course_content = make_test_course_content();
+        let outline = Outline::new(&course, &course_content, Some(&HashSet::new()));
+
+        let actual = outline.render(OutputFormat::Yaml);
+
+        assert!(actual.is_ok());
+        let actual = actual.unwrap();
+        assert!(actual.contains("schema_version: 1"));
+        assert!(actual.contains("title: The Lecture"));
+    }
+
+    #[test]
+    fn render_csv() {
+        let course = make_course();
+        let course_content = make_test_course_content();
+        let outline = Outline::new(&course, &course_content, Some(&HashSet::new()));
+
+        let actual = outline.render(OutputFormat::Csv);
+
+        assert!(actual.is_ok());
+        assert_eq!(
+            actual.unwrap(),
+            "chapter_index,chapter_title,lecture_id,lecture_index,title,asset_type,duration,supplementary_assets,completed\n\
+             1,The Chapter,4321,1,The Lecture,Video,147,0,false\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown() {
+        let course = make_course();
+        let course_content = make_test_course_content();
+        let outline = Outline::new(&course, &course_content, Some(&HashSet::new()));
+
+        let actual = outline.render(OutputFormat::Markdown);
+
+        assert!(actual.is_ok());
+        assert_eq!(
+            actual.unwrap(),
+            "# css-the-complete-guide-incl-flexbox-grid-sass\n\n\
+             1 chapters, 1 lectures, 2:27 (0 completed, 2:27 remaining)\n\n\
+             ## 001 The Chapter (2:27)\n\n\
+             - [ ] 001 The Lecture *Video 2:27*\n"
+        );
+    }
+
+    #[test]
+    fn render_unknown_progress() {
+        let course = make_course();
+        let course_content = make_test_course_content();
+        let outline = Outline::new(&course, &course_content, None);
+
+        let json: Value =
+            serde_json::from_str(outline.render(OutputFormat::Json).unwrap().as_str()).unwrap();
+        let csv = outline.render(OutputFormat::Csv).unwrap();
+        let markdown = outline.render(OutputFormat::Markdown).unwrap();
+
+        assert_eq!(json["completed_lecture_count"], Value::Null);
+        assert_eq!(json["remaining_duration"], Value::Null);
+        assert_eq!(json["chapters"][0]["lectures"][0]["completed"], Value::Null);
+        assert!(csv.ends_with("1,The Chapter,4321,1,The Lecture,Video,147,0,\n"));
+        assert!(markdown.contains("(progress unknown)"));
+        assert!(markdown.contains("- [?] 001 The Lecture"));
+    }
+}
@@ -1,14 +1,17 @@
-use failure::format_err;
 use serde_json::Value;
 
+use crate::error::with_json_path;
 use crate::model::*;
 use crate::result::Result;
-use crate::utils::{json_get_string, json_get_u64};
+use crate::utils::{json_error, json_get, json_get_string, json_get_u64};
 
 pub trait Parser {
     fn parse_subscribed_courses(&self, subscribed_courses: &Value) -> Result<Vec<Course>>;
     fn parse_course_content(&self, full_course: &Value) -> Result<CourseContent>;
     fn parse_lecture_detail(&self, lecture_detail: &Value) -> Result<LectureDetail>;
+    fn parse_user(&self, user: &Value) -> Result<User>;
+    fn parse_completed_lecture_ids(&self, progress: &Value) -> Result<Vec<LectureId>>;
+    fn parse_supplementary_asset(&self, asset: &Value) -> Result<Vec<DownloadUrl>>;
 }
 
 pub struct UdemyParser {}
@@ -25,28 +28,71 @@ impl UdemyParser {
         let title: String = json_get_string(asset, "title")?.into();
         let asset_type: String = json_get_string(asset, "asset_type")?.into();
         let time_estimation: u64 = json_get_u64(asset, "time_estimation")?;
-        let download_urls = asset
-            .get("download_urls")
-            .ok_or_else(|| format_err!("Error parsing json"))?;
-        let download_urls = if let Some(video) = download_urls.get("Video") {
-            Some(video)
-        } else if let Some(filee) = download_urls.get("File") {
-            Some(filee)
-        } else {
-            // println!("Unkonwn filetype {:?}", asset);
-            None
-        };
-
-        let download_urls: Option<Vec<DownloadUrl>> = if let Some(dl_urls) = download_urls {
-            Some(serde_json::from_value::<Vec<DownloadUrl>>(dl_urls.clone()).unwrap())
-        } else {
-            None
-        };
+        let download_urls = parse_download_urls(json_get(asset, "download_urls")?)?;
+        let thumbnail_url = asset
+            .get("thumbnail_url")
+            .and_then(Value::as_str)
+            .map(String::from);
+        let body = asset.get("body").and_then(Value::as_str).map(String::from);
+        let captions = asset
+            .get("captions")
+            .and_then(Value::as_array)
+            .map(|captions| captions.iter().filter_map(parse_caption).collect())
+            .unwrap_or_default();
         Ok(Asset {
             title,
             asset_type,
             time_estimation,
             download_urls,
+            thumbnail_url,
+            body,
+            captions,
+        })
+    }
+
+    /// Parse a chapter item of the course content.
+    fn parse_chapter(&self, item: &Value) -> Result<Chapter> {
+        Ok(Chapter {
+            object_index: json_get_u64(item, "object_index")?,
+            title: json_get_string(item, "title")?.into(),
+            description: item
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .into(),
+            lectures: Vec::new(),
+        })
+    }
+
+    /// Parse a lecture item of the course content.
+    fn parse_lecture(&self, item: &Value) -> Result<Lecture> {
+        let asset = json_get(item, "asset")?;
+        let filename = json_get_string(asset, "title")
+            .map_err(|e| with_json_path(e, "asset"))?
+            .into();
+        let asset_type: String = json_get_string(asset, "asset_type")
+            .map_err(|e| with_json_path(e, "asset"))?
+            .into();
+        let has_video = asset_type == "Video";
+        let duration = if has_video {
+            asset.get("length").and_then(Value::as_u64).unwrap_or(0)
+        } else {
+            0
+        };
+        let supplementary_assets = match item.get("supplementary_assets") {
+            Some(supplementary_assets) => serde_json::from_value(supplementary_assets.clone())
+                .map_err(|e| json_error("supplementary_assets", e.to_string().as_str()))?,
+            None => vec![],
+        };
+        Ok(Lecture {
+            has_video,
+            asset_type,
+            duration,
+            supplementary_assets,
+            filename,
+            id: json_get_u64(item, "id")?,
+            object_index: json_get_u64(item, "object_index")?,
+            title: json_get_string(item, "title")?.into(),
         })
     }
 }
@@ -54,11 +100,9 @@ impl UdemyParser {
 impl Parser for UdemyParser {
     /// Parse subscribed courses for this user.
     fn parse_subscribed_courses(&self, subscribed_courses: &Value) -> Result<Vec<Course>> {
-        let results = subscribed_courses
-            .get("results")
-            .ok_or_else(|| format_err!("Error parsing json"))?
+        let results = json_get(subscribed_courses, "results")?
             .as_array()
-            .ok_or_else(|| format_err!("Error parsing json"))?;
+            .ok_or_else(|| json_error("results", "not an array"))?;
         let courses: Vec<Course> = results
             .iter()
             .map(|result| serde_json::from_value(result.clone()))
@@ -70,43 +114,34 @@ impl Parser for UdemyParser {
 
     /// Parse full course content.
     fn parse_course_content(&self, full_course: &Value) -> Result<CourseContent> {
-        let results = full_course
-            .get("results")
-            .ok_or_else(|| format_err!("Error parsing json"))?
+        let results = json_get(full_course, "results")?
             .as_array()
-            .ok_or_else(|| format_err!("Error parsing json"))?;
+            .ok_or_else(|| json_error("results", "not an array"))?;
 
         let mut chapters: Vec<Chapter> = Vec::new();
         let mut lectures: Vec<Lecture> = Vec::new();
         let mut current_chapter: Option<Chapter> = None;
 
-        for item in results.iter() {
-            if item.get("_class").unwrap() == "chapter" {
+        for (index, item) in results.iter().enumerate() {
+            let path = format!("results[{}]", index);
+            let class = json_get_string(item, "_class").map_err(|e| with_json_path(e, &path))?;
+            if class == "chapter" {
                 if current_chapter.is_some() {
                     let mut this_chapter = current_chapter.unwrap();
                     this_chapter.lectures = lectures;
                     chapters.push(this_chapter);
                 }
-                current_chapter = Some(Chapter {
-                    object_index: json_get_u64(item, "object_index")?,
-                    title: json_get_string(item, "title")?.into(),
-                    lectures: Vec::new(),
-                });
+                current_chapter = Some(
+                    self.parse_chapter(item)
+                        .map_err(|e| with_json_path(e, &path))?,
+                );
                 lectures = Vec::new();
             }
-            if item.get("_class").unwrap() == "lecture" {
-                let asset = item
-                    .get("asset")
-                    .ok_or_else(|| format_err!("Error parsing json (asset)"))?;
-                let filename = json_get_string(asset, "title")?.into();
-                let has_video = json_get_string(asset, "asset_type")? == "Video";
-                lectures.push(Lecture {
-                    has_video,
-                    filename,
-                    id: json_get_u64(item, "id")?,
-                    object_index: json_get_u64(item, "object_index")?,
-                    title: json_get_string(item, "title")?.into(),
-                });
+            if class == "lecture" {
+                lectures.push(
+                    self.parse_lecture(item)
+                        .map_err(|e| with_json_path(e, &path))?,
+                );
             }
         }
         if current_chapter.is_some() {
@@ -118,16 +153,84 @@ impl Parser for UdemyParser {
     }
 
     fn parse_lecture_detail(&self, item: &Value) -> Result<LectureDetail> {
-        let asset = self.parse_asset(
-            item.get("asset")
-                .ok_or_else(|| format_err!("Error parsing json (assets)"))?,
-        )?;
+        let asset = self
+            .parse_asset(json_get(item, "asset")?)
+            .map_err(|e| with_json_path(e, "asset"))?;
         Ok(LectureDetail {
             id: json_get_u64(item, "id")?,
             title: json_get_string(item, "title")?.into(),
+            description: item
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .into(),
             asset,
         })
     }
+
+    /// Parse the authenticated user.
+    fn parse_user(&self, user: &Value) -> Result<User> {
+        Ok(User {
+            id: json_get_u64(user, "id")?,
+            title: json_get_string(user, "title")?.into(),
+        })
+    }
+
+    /// Parse the ids of the lectures completed by the user out of the course progress.
+    fn parse_completed_lecture_ids(&self, progress: &Value) -> Result<Vec<LectureId>> {
+        json_get(progress, "completed_lecture_ids")?
+            .as_array()
+            .ok_or_else(|| json_error("completed_lecture_ids", "not an array"))?
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                id.as_u64().ok_or_else(|| {
+                    json_error(
+                        format!("completed_lecture_ids[{}]", index).as_str(),
+                        "not an unsigned integer",
+                    )
+                    .into()
+                })
+            })
+            .collect()
+    }
+
+    /// Parse the download urls of a supplementary asset, none for links.
+    fn parse_supplementary_asset(&self, asset: &Value) -> Result<Vec<DownloadUrl>> {
+        Ok(parse_download_urls(json_get(asset, "download_urls")?)?.unwrap_or_default())
+    }
+}
+
+/// Parse the download urls of an asset, those of its video or of its file.
+fn parse_download_urls(download_urls: &Value) -> Result<Option<Vec<DownloadUrl>>> {
+    let download_urls = if let Some(video) = download_urls.get("Video") {
+        video
+    } else if let Some(file) = download_urls.get("File") {
+        file
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(
+        serde_json::from_value::<Vec<DownloadUrl>>(download_urls.clone())
+            .map_err(|e| json_error("download_urls", e.to_string().as_str()))?,
+    ))
+}
+
+/// Parse a caption of an asset, skipping the ones without locale or url.
+fn parse_caption(caption: &Value) -> Option<Caption> {
+    Some(Caption {
+        locale: caption.get("locale")?.get("locale")?.as_str()?.into(),
+        label: caption
+            .get("video_label")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .into(),
+        url: caption.get("url")?.as_str()?.into(),
+        is_translation: caption
+            .get("is_translation")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+    })
 }
 
 #[cfg(test)]
@@ -200,6 +303,99 @@ mod test_udemy_downloader {
             "What Is Ionic?"
         );
         assert_eq!(course_content.chapters[4].lectures[3].has_video, false);
+        assert_eq!(course_content.chapters[4].lectures[3].asset_type, "Article");
+        assert_eq!(course_content.chapters[4].lectures[3].duration, 0);
+        assert_eq!(course_content.chapters[0].lectures[0].asset_type, "Video");
+        assert_eq!(course_content.chapters[0].lectures[0].duration, 147);
+        assert_eq!(course_content.chapters[0].lectures[1].duration, 464);
+    }
+
+    #[test]
+    fn parse_course_content_with_invalid_lecture() {
+        let full_course: Value = serde_json::from_str(
+            r#"{"results": [
+                {"_class": "chapter", "object_index": 1, "title": "The Chapter"},
+                {"_class": "lecture", "id": 4321, "object_index": 1, "title": "The Lecture",
+                 "asset": {"asset_type": "Video"}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let parser = UdemyParser::new();
+
+        let actual = parser.parse_course_content(&full_course);
+
+        assert!(actual.is_err());
+        assert_eq!(
+            actual.unwrap_err().to_string(),
+            "Error parsing json (results[1].asset.title): missing field"
+        );
+    }
+
+    #[test]
+    fn parse_user() {
+        let user: Value = serde_json::from_str(
+            r#"{"_class": "user", "id": 1234, "title": "John Doe", "display_name": "John Doe"}"#,
+        )
+        .unwrap();
+
+        let parser = UdemyParser::new();
+
+        let actual = parser.parse_user(&user);
+
+        assert!(actual.is_ok());
+        let user = actual.unwrap();
+        assert_eq!(user.id, 1234);
+        assert_eq!(user.title, "John Doe");
+    }
+
+    #[test]
+    fn parse_completed_lecture_ids() {
+        let progress: Value = serde_json::from_str(
+            r#"{"completed_lecture_ids": [4321, 4322], "completed_quiz_ids": [],
+                "completed_assignment_ids": [], "last_seen_page": null}"#,
+        )
+        .unwrap();
+
+        let parser = UdemyParser::new();
+
+        let actual = parser.parse_completed_lecture_ids(&progress);
+
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), vec![4321, 4322]);
+    }
+
+    #[test]
+    fn parse_completed_lecture_ids_invalid() {
+        let progress: Value =
+            serde_json::from_str(r#"{"completed_lecture_ids": [4321, "x"]}"#).unwrap();
+
+        let parser = UdemyParser::new();
+
+        let actual = parser.parse_completed_lecture_ids(&progress);
+
+        assert!(actual.is_err());
+        assert_eq!(
+            actual.unwrap_err().to_string(),
+            "Error parsing json (completed_lecture_ids[1]): not an unsigned integer"
+        );
+    }
+
+    #[test]
+    fn parse_supplementary_asset() {
+        let file: Value = serde_json::from_str(
+            r#"{"_class": "asset", "id": 1, "download_urls": {"File": [{"label": "download", "file": "https://host-name/exercise.zip"}]}}"#,
+        )
+        .unwrap();
+        let link: Value =
+            serde_json::from_str(r#"{"_class": "asset", "id": 2, "download_urls": null}"#).unwrap();
+
+        let parser = UdemyParser::new();
+
+        let actual = parser.parse_supplementary_asset(&file).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].file, "https://host-name/exercise.zip");
+        assert!(parser.parse_supplementary_asset(&link).unwrap().is_empty());
     }
 
     #[test]
@@ -235,4 +431,27 @@ mod test_udemy_downloader {
         assert_eq!(asset.download_urls.as_ref().unwrap()[2].label, "360");
         assert_eq!(asset.download_urls.as_ref().unwrap()[3].label, "144");
     }
+
+    #[test]
+    fn parse_lecture_captions() {
+        let lecture_detail =
+            fs::read_to_string("test-data/subscribed-courses-lecture.json").unwrap();
+        let lecture_detail = serde_json::from_str(lecture_detail.as_str()).unwrap();
+
+        let parser = UdemyParser::new();
+
+        let captions = parser
+            .parse_lecture_detail(&lecture_detail)
+            .unwrap()
+            .asset
+            .captions;
+
+        assert!(!captions.is_empty());
+        assert_eq!(captions[0].locale, "th_TH");
+        assert_eq!(captions[0].label, "Thai [Auto]");
+        assert!(captions[0].is_translation);
+        assert!(captions[0]
+            .url
+            .starts_with("https://udemy-captions.s3.amazonaws.com"));
+    }
 }
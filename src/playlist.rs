@@ -0,0 +1,71 @@
+/// Extension of the playlists written along downloads.
+pub const PLAYLIST_EXTENSION: &str = "m3u8";
+
+/// Media of a playlist, its path being relative to the playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    pub duration: u64,
+    pub title: String,
+    pub path: String,
+}
+
+/// Render an extended M3U playlist, in UTF-8. Unknown durations, of zero
+/// seconds, are written as -1.
+pub fn render_m3u8(entries: &[PlaylistEntry]) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for entry in entries {
+        let duration = if entry.duration > 0 {
+            entry.duration.to_string()
+        } else {
+            "-1".into()
+        };
+        playlist.push_str(
+            format!(
+                "#EXTINF:{},{}\n{}\n",
+                duration,
+                single_line(entry.title.as_str()),
+                entry.path
+            )
+            .as_str(),
+        );
+    }
+    playlist
+}
+
+/// Join the parts of a relative path with forward slashes, which all players
+/// understand.
+pub fn relative_path(parts: &[&str]) -> String {
+    parts.join("/")
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_playlist() {
+        let entries = vec![
+            PlaylistEntry {
+                duration: 147,
+                title: "Welcome".into(),
+                path: relative_path(&["001 Getting Started", "001 Welcome.mp4"]),
+            },
+            PlaylistEntry {
+                duration: 0,
+                title: "What is\nCSS?".into(),
+                path: relative_path(&["001 Getting Started", "002 What is CSS.m4a"]),
+            },
+        ];
+
+        let actual = render_m3u8(&entries);
+
+        assert_eq!(
+            actual,
+            "#EXTM3U\n#EXTINF:147,Welcome\n001 Getting Started/001 Welcome.mp4\n#EXTINF:-1,What is CSS?\n001 Getting Started/002 What is CSS.m4a\n"
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+use std::str::FromStr;
+
+use failure::format_err;
+use indicatif::HumanBytes;
+
+use crate::model::DownloadUrl;
+use crate::result::Result;
+use crate::utils::parse_size;
+
+/// One way of choosing among the qualities of a lecture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityRule {
+    /// The highest resolution.
+    Best,
+    /// The lowest resolution.
+    Worst,
+    /// The highest resolution up to a height.
+    AtMost(u64),
+    /// The lowest resolution from a height.
+    AtLeast(u64),
+    /// The resolution closest to a height, the higher one on ties.
+    Closest(u64),
+    /// The highest resolution whose file fits in a number of bytes.
+    MaxSize(u64),
+}
+
+/// Chain of quality rules, each one being tried when the previous ones found
+/// nothing, such as `<=720,worst`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityPolicy {
+    rules: Vec<QualityRule>,
+}
+
+impl Default for QualityPolicy {
+    fn default() -> QualityPolicy {
+        QualityPolicy {
+            rules: vec![QualityRule::Best],
+        }
+    }
+}
+
+impl QualityPolicy {
+    /// Choose a download url, telling which rule chose it. The size of a url is
+    /// only asked for by `maxsize` rules.
+    pub fn select<'u>(
+        &self,
+        download_urls: &'u [DownloadUrl],
+        size_of: &mut dyn FnMut(&DownloadUrl) -> Result<u64>,
+    ) -> Result<(&'u DownloadUrl, QualityRule)> {
+        let candidates = candidates(download_urls);
+        for rule in self.rules.iter() {
+            if let Some(url) = rule.select(&candidates, size_of) {
+                return Ok((url, *rule));
+            }
+        }
+        Err(format_err!(
+            "No quality matching {} among {}",
+            self,
+            candidates
+                .iter()
+                .map(|url| url.label.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        ))
+    }
+}
+
+impl QualityRule {
+    fn select<'u>(
+        &self,
+        candidates: &[&'u DownloadUrl],
+        size_of: &mut dyn FnMut(&DownloadUrl) -> Result<u64>,
+    ) -> Option<&'u DownloadUrl> {
+        let numeric = || {
+            candidates
+                .iter()
+                .filter_map(|url| height(url).map(|height| (height, *url)))
+        };
+        let has_numeric = numeric().next().is_some();
+        match *self {
+            // A lone url without a resolution, as for `File` assets, is taken by
+            // any rule on heights.
+            QualityRule::AtMost(_) | QualityRule::AtLeast(_) | QualityRule::Closest(_)
+                if !has_numeric && candidates.len() == 1 =>
+            {
+                candidates.first().cloned()
+            }
+            QualityRule::Best if has_numeric => numeric().max_by_key(|(h, _)| *h).map(|(_, u)| u),
+            QualityRule::Worst if has_numeric => numeric().min_by_key(|(h, _)| *h).map(|(_, u)| u),
+            // Labels such as `download` or `Auto` do not tell a resolution.
+            QualityRule::Best | QualityRule::Worst => candidates.first().cloned(),
+            QualityRule::AtMost(max) => numeric()
+                .filter(|(h, _)| *h <= max)
+                .max_by_key(|(h, _)| *h)
+                .map(|(_, u)| u),
+            QualityRule::AtLeast(min) => numeric()
+                .filter(|(h, _)| *h >= min)
+                .min_by_key(|(h, _)| *h)
+                .map(|(_, u)| u),
+            QualityRule::Closest(target) => numeric()
+                .min_by_key(|(h, _)| {
+                    let distance = if *h > target { h - target } else { target - h };
+                    (distance, std::cmp::Reverse(*h))
+                })
+                .map(|(_, u)| u),
+            QualityRule::MaxSize(max) => {
+                let mut by_height: Vec<&DownloadUrl> = candidates.to_vec();
+                by_height.sort_by_key(|url| std::cmp::Reverse(height(url)));
+                by_height
+                    .into_iter()
+                    .find(|url| size_of(url).map(|size| size <= max).unwrap_or(false))
+            }
+        }
+    }
+}
+
+/// Urls to choose from: mp4 videos when there are, all of them otherwise, as
+/// for `File` assets.
+fn candidates(download_urls: &[DownloadUrl]) -> Vec<&DownloadUrl> {
+    let videos: Vec<&DownloadUrl> = download_urls
+        .iter()
+        .filter(|url| url.r#type.as_deref() == Some("video/mp4"))
+        .collect();
+    if videos.is_empty() {
+        download_urls.iter().collect()
+    } else {
+        videos
+    }
+}
+
+/// Height of a quality label such as `720` or `720p`.
+fn height(url: &DownloadUrl) -> Option<u64> {
+    url.label.trim().trim_end_matches('p').parse::<u64>().ok()
+}
+
+impl FromStr for QualityRule {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<QualityRule> {
+        let s = s.trim();
+        let height = |value: &str| {
+            value
+                .trim()
+                .trim_end_matches('p')
+                .parse::<u64>()
+                .map_err(|_| format_err!("Invalid height <{}> in quality <{}>", value, s))
+        };
+        let lower = s.to_lowercase();
+        if lower == "best" {
+            Ok(QualityRule::Best)
+        } else if lower == "worst" {
+            Ok(QualityRule::Worst)
+        } else if lower.starts_with("<=") {
+            Ok(QualityRule::AtMost(height(&s[2..])?))
+        } else if lower.starts_with(">=") {
+            Ok(QualityRule::AtLeast(height(&s[2..])?))
+        } else if lower.starts_with("closest:") {
+            Ok(QualityRule::Closest(height(&s["closest:".len()..])?))
+        } else if lower.starts_with("maxsize:") {
+            let size = s["maxsize:".len()..].trim_end_matches(['B', 'b']);
+            Ok(QualityRule::MaxSize(parse_size(size).map_err(|_| {
+                format_err!("Invalid size in quality <{}>", s)
+            })?))
+        } else {
+            // A plain height keeps its former meaning, the lowest from it.
+            Ok(QualityRule::AtLeast(height(s)?))
+        }
+    }
+}
+
+impl FromStr for QualityPolicy {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<QualityPolicy> {
+        let rules = s
+            .split(',')
+            .map(str::parse::<QualityRule>)
+            .collect::<Result<Vec<QualityRule>>>()?;
+        Ok(QualityPolicy { rules })
+    }
+}
+
+impl fmt::Display for QualityRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QualityRule::Best => write!(f, "best"),
+            QualityRule::Worst => write!(f, "worst"),
+            QualityRule::AtMost(height) => write!(f, "<={}", height),
+            QualityRule::AtLeast(height) => write!(f, ">={}", height),
+            QualityRule::Closest(height) => write!(f, "closest:{}", height),
+            QualityRule::MaxSize(size) => write!(f, "maxsize:{}", HumanBytes(*size)),
+        }
+    }
+}
+
+impl fmt::Display for QualityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules: Vec<String> = self.rules.iter().map(QualityRule::to_string).collect();
+        write!(f, "{}", rules.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn video(label: &str) -> DownloadUrl {
+        DownloadUrl {
+            label: label.into(),
+            file: format!("the-file-video-{}", label),
+            r#type: Some("video/mp4".into()),
+        }
+    }
+
+    fn videos() -> Vec<DownloadUrl> {
+        vec![
+            video("480"),
+            video("360"),
+            video("720"),
+            video("1080"),
+            DownloadUrl {
+                label: "1720".into(),
+                file: "the-file-720".into(),
+                r#type: Some("other/mp4".into()),
+            },
+        ]
+    }
+
+    fn select(policy: &str, download_urls: &[DownloadUrl]) -> Result<String> {
+        let policy = policy.parse::<QualityPolicy>()?;
+        let mut size_of = |url: &DownloadUrl| Ok(height(url).unwrap_or(0) * 100_000);
+        policy
+            .select(download_urls, &mut size_of)
+            .map(|(url, _)| url.label.clone())
+    }
+
+    #[test]
+    fn parse_policy() {
+        let actual = "closest:720p, maxsize:200MB,worst"
+            .parse::<QualityPolicy>()
+            .unwrap();
+
+        assert_eq!(
+            actual.rules,
+            vec![
+                QualityRule::Closest(720),
+                QualityRule::MaxSize(200 * 1024 * 1024),
+                QualityRule::Worst
+            ]
+        );
+        assert_eq!(
+            "480".parse::<QualityRule>().unwrap(),
+            QualityRule::AtLeast(480)
+        );
+        assert!("<=high".parse::<QualityPolicy>().is_err());
+        assert!("maxsize:lots".parse::<QualityPolicy>().is_err());
+    }
+
+    #[test]
+    fn select_best_and_worst() {
+        assert_eq!(select("best", &videos()).unwrap(), "1080");
+        assert_eq!(select("worst", &videos()).unwrap(), "360");
+    }
+
+    #[test]
+    fn select_bounds() {
+        assert_eq!(select("<=720", &videos()).unwrap(), "720");
+        assert_eq!(select("<=700", &videos()).unwrap(), "480");
+        assert_eq!(select(">=470", &videos()).unwrap(), "480");
+        assert_eq!(select("480", &videos()).unwrap(), "480");
+        assert!(select(">=1440", &videos()).is_err());
+    }
+
+    #[test]
+    fn select_closest() {
+        assert_eq!(select("closest:600", &videos()).unwrap(), "720");
+        assert_eq!(select("closest:400", &videos()).unwrap(), "360");
+    }
+
+    #[test]
+    fn select_closest_on_ties() {
+        let lower_first = vec![video("480"), video("720")];
+        let higher_first = vec![video("720"), video("480")];
+
+        assert_eq!(select("closest:600", &lower_first).unwrap(), "720");
+        assert_eq!(select("closest:600", &higher_first).unwrap(), "720");
+    }
+
+    #[test]
+    fn select_max_size() {
+        assert_eq!(select("maxsize:75M", &videos()).unwrap(), "720");
+        assert!(select("maxsize:1K", &videos()).is_err());
+    }
+
+    #[test]
+    fn select_with_fallback() {
+        let policy = ">=1440,<=720".parse::<QualityPolicy>().unwrap();
+        let download_urls = videos();
+
+        let (url, rule) = policy.select(&download_urls, &mut |_| Ok(0)).unwrap();
+
+        assert_eq!(url.label, "720");
+        assert_eq!(rule, QualityRule::AtMost(720));
+    }
+
+    #[test]
+    fn select_file_asset() {
+        let download_urls = vec![DownloadUrl {
+            label: "download".into(),
+            file: "https://host-name/original.zip".into(),
+            r#type: None,
+        }];
+
+        assert_eq!(select("best", &download_urls).unwrap(), "download");
+        assert_eq!(select("<=720,worst", &download_urls).unwrap(), "download");
+        assert_eq!(select("720", &download_urls).unwrap(), "download");
+        assert_eq!(select("closest:720", &download_urls).unwrap(), "download");
+        assert_eq!(select("maxsize:1K", &download_urls).unwrap(), "download");
+    }
+
+    #[test]
+    fn select_among_labels_without_height() {
+        let download_urls = vec![video("Auto"), video("download")];
+
+        assert_eq!(select("best", &download_urls).unwrap(), "Auto");
+        assert!(select("720", &download_urls).is_err());
+    }
+}
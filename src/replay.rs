@@ -0,0 +1,306 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use failure::format_err;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::fs_helper::FsHelper;
+use crate::http_client::HttpClient;
+use crate::model::Auth;
+use crate::result::Result;
+use crate::utils::sanitize;
+
+/// Query parameters holding secrets, redacted from urls and recorded responses.
+const SECRET_PARAMS: &[&str] = &[
+    "token",
+    "access_token",
+    "Signature",
+    "Key-Pair-Id",
+    "Policy",
+];
+/// Query parameters changing from one request to the other, left out of keys.
+const VOLATILE_PARAMS: &[&str] = &["nva", "Expires"];
+const REDACTED: &str = "REDACTED";
+
+/// Secret query parameters found in response bodies, built once.
+static SECRET_PARAMS_RE: OnceLock<Regex> = OnceLock::new();
+
+/// Normalise a url so that the same request always gets the same key: query
+/// parameters are sorted, secrets redacted and expiry parameters dropped.
+pub fn normalize_url(url: &str) -> String {
+    let (base, query) = match url.find('?') {
+        Some(position) => (&url[..position], &url[position + 1..]),
+        None => (url, ""),
+    };
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .filter_map(|param| {
+            let name = param.split('=').next().unwrap_or(param);
+            if VOLATILE_PARAMS.contains(&name) {
+                None
+            } else if SECRET_PARAMS.contains(&name) {
+                Some(format!("{}={}", name, REDACTED))
+            } else {
+                Some(param.into())
+            }
+        })
+        .collect();
+    params.sort();
+    let base = base.to_lowercase();
+    if params.is_empty() {
+        base
+    } else {
+        format!("{}?{}", base, params.join("&"))
+    }
+}
+
+/// Key under which the response to a url is saved. The readable part comes from
+/// the api path, the hash of the normalised url tells queries apart.
+pub fn url_key(url: &str) -> String {
+    let normalized = normalize_url(url);
+    let path = normalized.split('?').next().unwrap_or("");
+    let path = match path.find("/api-2.0/") {
+        Some(position) => &path[position + "/api-2.0/".len()..],
+        None => path
+            .rsplit('/')
+            .find(|segment| !segment.is_empty())
+            .unwrap_or(""),
+    };
+    let name = sanitize(path.trim_matches('/').replace('/', "-").as_str()).replace(' ', "_");
+    format!("{}-{:016x}", name, fnv1a(normalized.as_bytes()))
+}
+
+/// Redact secrets from a response body.
+pub fn redact(text: &str) -> String {
+    let re = SECRET_PARAMS_RE.get_or_init(|| {
+        Regex::new(&format!(
+            r"([?&](?:{})=)[^&\x22\\\s]*",
+            SECRET_PARAMS.join("|")
+        ))
+        .unwrap()
+    });
+    re.replace_all(text, format!("${{1}}{}", REDACTED).as_str())
+        .into_owned()
+}
+
+/// 64 bits FNV-1a hash, stable across runs and platforms.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn key_path(dir: &str, url: &str, extension: &str) -> String {
+    let mut path_buf = PathBuf::from(dir);
+    path_buf.push(format!("{}.{}", url_key(url), extension));
+    path_buf.to_string_lossy().into()
+}
+
+/// Http client saving every api response to a directory, on top of another client.
+pub struct RecordingHttpClient<'a> {
+    client: &'a dyn HttpClient,
+    fs_helper: &'a dyn FsHelper,
+    dir: String,
+}
+
+impl<'a> RecordingHttpClient<'a> {
+    pub fn new(
+        client: &'a dyn HttpClient,
+        fs_helper: &'a dyn FsHelper,
+        dir: &str,
+    ) -> RecordingHttpClient<'a> {
+        RecordingHttpClient {
+            client,
+            fs_helper,
+            dir: dir.into(),
+        }
+    }
+
+    fn save(&self, url: &str, extension: &str, data: &[u8]) -> Result<()> {
+        self.fs_helper.create_dir_recursive(self.dir.as_str())?;
+        self.fs_helper
+            .write_file(key_path(self.dir.as_str(), url, extension).as_str(), data)
+    }
+}
+
+impl<'a> HttpClient for RecordingHttpClient<'a> {
+    fn get_as_text(&self, url: &str, auth: &Auth) -> Result<String> {
+        let text = self.client.get_as_text(url, auth)?;
+        self.save(url, "json", redact(text.as_str()).as_bytes())?;
+        Ok(text)
+    }
+
+    fn get_as_data(&self, url: &str, buf: &mut Vec<u8>, f: &mut dyn FnMut(u64)) -> Result<()> {
+        self.client.get_as_data(url, buf, f)
+    }
+
+    fn get_content_length(&self, url: &str) -> Result<u64> {
+        let content_length = self.client.get_content_length(url)?;
+        self.save(url, "length", content_length.to_string().as_bytes())?;
+        Ok(content_length)
+    }
+
+    fn post_json(&self, url: &str, json: &Value, auth: &Auth) -> Result<()> {
+        self.client.post_json(url, json, auth)
+    }
+
+    fn delete(&self, url: &str, auth: &Auth) -> Result<()> {
+        self.client.delete(url, auth)
+    }
+}
+
+/// Http client answering from responses previously saved by `RecordingHttpClient`,
+/// without any network access.
+pub struct ReplayHttpClient<'a> {
+    fs_helper: &'a dyn FsHelper,
+    dir: String,
+}
+
+impl<'a> ReplayHttpClient<'a> {
+    pub fn new(fs_helper: &'a dyn FsHelper, dir: &str) -> ReplayHttpClient<'a> {
+        ReplayHttpClient {
+            fs_helper,
+            dir: dir.into(),
+        }
+    }
+
+    fn read(&self, url: &str, extension: &str) -> Result<String> {
+        let path = key_path(self.dir.as_str(), url, extension);
+        self.fs_helper.read_file(path.as_str()).map_err(|e| {
+            format_err!(
+                "No recorded response for url <{}> in <{}>: {}",
+                normalize_url(url),
+                self.dir,
+                e
+            )
+        })
+    }
+}
+
+impl<'a> HttpClient for ReplayHttpClient<'a> {
+    fn get_as_text(&self, url: &str, _auth: &Auth) -> Result<String> {
+        self.read(url, "json")
+    }
+
+    fn get_as_data(&self, url: &str, _buf: &mut Vec<u8>, _f: &mut dyn FnMut(u64)) -> Result<()> {
+        Err(format_err!(
+            "Media are not available in replay mode <{}>",
+            normalize_url(url)
+        ))
+    }
+
+    fn get_content_length(&self, url: &str) -> Result<u64> {
+        let length = self.read(url, "length")?;
+        length
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format_err!("Invalid recorded length for url <{}>", url))
+    }
+
+    fn post_json(&self, url: &str, _json: &Value, _auth: &Auth) -> Result<()> {
+        Err(format_err!(
+            "Changes can not be sent in replay mode <{}>",
+            normalize_url(url)
+        ))
+    }
+
+    fn delete(&self, url: &str, _auth: &Auth) -> Result<()> {
+        Err(format_err!(
+            "Changes can not be sent in replay mode <{}>",
+            normalize_url(url)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mocks::test::*;
+
+    const LECTURE_URL: &str = "https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/lectures/4321?fields[asset]=@min,download_urls&fields[lecture]=@default";
+
+    #[test]
+    fn normalize_url_sorts_and_redacts() {
+        let actual =
+            normalize_url("https://WWW.udemy.com/file.mp4?token=secret&nva=20190129190000&b=2&a=1");
+
+        assert_eq!(
+            actual,
+            "https://www.udemy.com/file.mp4?a=1&b=2&token=REDACTED"
+        );
+    }
+
+    #[test]
+    fn url_key_is_stable() {
+        let actual = url_key(LECTURE_URL);
+
+        assert!(actual.starts_with("users-me-subscribed-courses-54321-lectures-4321-"));
+        assert_eq!(actual, url_key(LECTURE_URL));
+        assert_ne!(
+            actual,
+            url_key(
+                "https://www.udemy.com/api-2.0/users/me/subscribed-courses/54321/lectures/4321"
+            )
+        );
+    }
+
+    #[test]
+    fn url_key_ignores_expiry() {
+        assert_eq!(
+            url_key("https://host/file.mp4?nva=1&token=one"),
+            url_key("https://host/file.mp4?token=two&nva=2")
+        );
+    }
+
+    #[test]
+    fn redact_body() {
+        let actual = redact(
+            r#"{"file": "https://mp4-a.udemycdn.com/file.mp4?nva=20190129190000&token=0a5f8c", "label": "720"}"#,
+        );
+
+        assert_eq!(
+            actual,
+            r#"{"file": "https://mp4-a.udemycdn.com/file.mp4?nva=20190129190000&token=REDACTED", "label": "720"}"#
+        );
+    }
+
+    #[test]
+    fn record() {
+        let _mocks = lock_mocks();
+        unsafe {
+            GETS_AS_JSON_URL = Some(vec![]);
+            WRITE_FILE_PATH = Some(vec![]);
+        }
+        let fs_helper = MockFsHelper {};
+        let mock_http_client = MockHttpClient {};
+        let client = RecordingHttpClient::new(&mock_http_client, &fs_helper, "records");
+
+        let actual = client.get_as_text(LECTURE_URL, &Auth::with_token("blah"));
+
+        assert!(actual.is_ok());
+        unsafe {
+            if let Some(ref wfp) = WRITE_FILE_PATH {
+                assert_eq!(wfp.len(), 1);
+                assert_eq!(wfp[0], format!("records/{}.json", url_key(LECTURE_URL)));
+            }
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let _mocks = lock_mocks();
+        let fs_helper = MockFsHelper {};
+        let client = ReplayHttpClient::new(&fs_helper, "records");
+
+        let actual = client.get_as_json(LECTURE_URL, &Auth::with_token("blah"));
+
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), Value::Array(vec![]));
+        assert!(client
+            .get_as_data(LECTURE_URL, &mut vec![], &mut |_| {})
+            .is_err());
+    }
+}
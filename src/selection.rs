@@ -0,0 +1,81 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use failure::format_err;
+
+use crate::model::ObjectIndex;
+
+/// Selection of object indexes given as a comma separated list of indexes and
+/// ranges, such as `1-3,5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    ranges: Vec<RangeInclusive<ObjectIndex>>,
+}
+
+impl Selection {
+    pub fn contains(&self, index: ObjectIndex) -> bool {
+        self.ranges.iter().any(|range| range.contains(&index))
+    }
+}
+
+impl FromStr for Selection {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Selection, Self::Err> {
+        let parse_index = |index: &str| {
+            index
+                .trim()
+                .parse::<ObjectIndex>()
+                .map_err(|_| format_err!("Invalid index <{}> in selection <{}>", index, s))
+        };
+        let mut ranges = vec![];
+        for part in s.split(',') {
+            let range = match part.find('-') {
+                Some(position) => {
+                    parse_index(&part[..position])?..=parse_index(&part[position + 1..])?
+                }
+                None => {
+                    let index = parse_index(part)?;
+                    index..=index
+                }
+            };
+            if range.is_empty() {
+                return Err(format_err!("Invalid range <{}> in selection <{}>", part, s));
+            }
+            ranges.push(range);
+        }
+        Ok(Selection { ranges })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_single_index() {
+        let actual = "3".parse::<Selection>().unwrap();
+
+        assert!(actual.contains(3));
+        assert!(!actual.contains(2));
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let actual = "1-3, 5".parse::<Selection>().unwrap();
+
+        assert_eq!(
+            (1..=6)
+                .filter(|i| actual.contains(*i))
+                .collect::<Vec<u64>>(),
+            vec![1, 2, 3, 5]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("".parse::<Selection>().is_err());
+        assert!("1-a".parse::<Selection>().is_err());
+        assert!("5-3".parse::<Selection>().is_err());
+    }
+}
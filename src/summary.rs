@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use indicatif::{HumanBytes, HumanDuration};
+use serde_derive::Serialize;
+
+use crate::model::*;
+use crate::result::Result;
+
+/// What happened to a lecture during a run.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Downloaded,
+    Completed,
+    Uncompleted,
+    Skipped,
+    Failed,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Downloaded => "downloaded",
+            Status::Completed => "completed",
+            Status::Uncompleted => "uncompleted",
+            Status::Skipped => "skipped",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+/// Outcome of processing a single lecture.
+#[derive(Serialize, Debug, Clone)]
+pub struct LectureOutcome {
+    pub chapter_index: ObjectIndex,
+    pub chapter_title: String,
+    pub lecture_index: ObjectIndex,
+    pub lecture_id: LectureId,
+    pub title: String,
+    pub status: Status,
+    pub reason: Option<String>,
+    pub bytes: u64,
+    pub duration_ms: u64,
+}
+
+impl LectureOutcome {
+    pub fn new(chapter: &Chapter, lecture: &Lecture, status: Status) -> LectureOutcome {
+        LectureOutcome {
+            chapter_index: chapter.object_index,
+            chapter_title: chapter.title.clone(),
+            lecture_index: lecture.object_index,
+            lecture_id: lecture.id,
+            title: lecture.title.clone(),
+            status,
+            reason: None,
+            bytes: 0,
+            duration_ms: 0,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: &str) -> LectureOutcome {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: u64) -> LectureOutcome {
+        self.bytes = bytes;
+        self
+    }
+}
+
+/// Outcomes of all the lectures processed by a command.
+#[derive(Debug)]
+pub struct Summary {
+    pub outcomes: Vec<LectureOutcome>,
+}
+
+/// Json representation of a summary, with totals computed up front.
+#[derive(Serialize)]
+struct SummaryReport<'a> {
+    total: usize,
+    downloaded: usize,
+    completed: usize,
+    uncompleted: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    duration_ms: u64,
+    outcomes: &'a [LectureOutcome],
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary { outcomes: vec![] }
+    }
+
+    pub fn push(&mut self, outcome: LectureOutcome) {
+        self.outcomes.push(outcome);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+
+    pub fn total(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn count(&self, status: Status) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.status == status)
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(Status::Failed)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.outcomes.iter().map(|outcome| outcome.bytes).sum()
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.outcomes
+            .iter()
+            .map(|outcome| outcome.duration_ms)
+            .sum()
+    }
+
+    /// Print a table with one line per lecture followed by the totals.
+    pub fn print(&self) {
+        println!();
+        println!(
+            "{:>7} {:>7}  {:<10} {:>10} {:>10}  Title",
+            "Chapter", "Lecture", "Status", "Size", "Time"
+        );
+        for outcome in self.outcomes.iter() {
+            let title = match outcome.reason {
+                Some(ref reason) => format!("{} ({})", outcome.title, reason),
+                None => outcome.title.clone(),
+            };
+            println!(
+                "{:>7} {:>7}  {:<10} {:>10} {:>10}  {}",
+                format!("{:03}", outcome.chapter_index),
+                format!("{:03}", outcome.lecture_index),
+                outcome.status.as_str(),
+                format!("{}", HumanBytes(outcome.bytes)),
+                format!(
+                    "{}",
+                    HumanDuration(Duration::from_millis(outcome.duration_ms))
+                ),
+                title
+            );
+        }
+        println!(
+            "Downloaded: {}, Completed: {}, Uncompleted: {}, Skipped: {}, Failed: {}, Total: {} ({} in {})",
+            self.count(Status::Downloaded),
+            self.count(Status::Completed),
+            self.count(Status::Uncompleted),
+            self.count(Status::Skipped),
+            self.failed(),
+            self.total(),
+            HumanBytes(self.bytes()),
+            HumanDuration(Duration::from_millis(self.duration_ms()))
+        );
+    }
+
+    /// Serialize the summary, totals included, as json.
+    pub fn to_json(&self) -> Result<String> {
+        let report = SummaryReport {
+            total: self.total(),
+            downloaded: self.count(Status::Downloaded),
+            completed: self.count(Status::Completed),
+            uncompleted: self.count(Status::Uncompleted),
+            skipped: self.count(Status::Skipped),
+            failed: self.failed(),
+            bytes: self.bytes(),
+            duration_ms: self.duration_ms(),
+            outcomes: &self.outcomes,
+        };
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::Value;
+
+    use crate::test_data::test::*;
+
+    fn make_summary() -> Summary {
+        let course_content = make_test_course_content();
+        let chapter = &course_content.chapters[0];
+        let lecture = &chapter.lectures[0];
+        let mut summary = Summary::new();
+        summary.push(LectureOutcome::new(chapter, lecture, Status::Downloaded).with_bytes(321));
+        summary.push(LectureOutcome::new(chapter, lecture, Status::Skipped).with_reason("dry run"));
+        summary.push(
+            LectureOutcome::new(chapter, lecture, Status::Failed).with_reason("Error received"),
+        );
+        summary
+    }
+
+    #[test]
+    fn counts() {
+        let summary = make_summary();
+
+        assert_eq!(summary.total(), 3);
+        assert_eq!(summary.count(Status::Downloaded), 1);
+        assert_eq!(summary.count(Status::Skipped), 1);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(summary.bytes(), 321);
+    }
+
+    #[test]
+    fn to_json() {
+        let summary = make_summary();
+
+        let actual = summary.to_json();
+
+        assert!(actual.is_ok());
+        let actual: Value = serde_json::from_str(actual.unwrap().as_str()).unwrap();
+        assert_eq!(actual["total"], 3);
+        assert_eq!(actual["failed"], 1);
+        assert_eq!(actual["bytes"], 321);
+        assert_eq!(actual["outcomes"][0]["status"], "downloaded");
+        assert_eq!(actual["outcomes"][0]["lecture_id"], 4321);
+        assert_eq!(actual["outcomes"][2]["reason"], "Error received");
+    }
+}
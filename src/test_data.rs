@@ -7,6 +7,12 @@ pub mod test {
             id: 54321,
             url: "the-url".into(),
             published_title: "css-the-complete-guide-incl-flexbox-grid-sass".into(),
+            title: "CSS - The Complete Guide".into(),
+            visible_instructors: vec![Instructor {
+                title: "Maximilian Schwarzmüller".into(),
+            }],
+            headline: "Learn CSS from the ground up".into(),
+            image_url: Some("https://img-a.udemycdn.com/course/480x270/1561458_7f3b.jpg".into()),
         }
     }
 
@@ -15,8 +21,12 @@ pub mod test {
             chapters: vec![Chapter {
                 object_index: 1,
                 title: "The Chapter".into(),
+                description: "<p>What the chapter is about.</p>".into(),
                 lectures: vec![Lecture {
                     has_video: true,
+                    asset_type: "Video".into(),
+                    duration: 147,
+                    supplementary_assets: vec![],
                     filename: "blah-blah.mp4".into(),
                     id: 4321,
                     object_index: 1,
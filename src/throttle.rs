@@ -0,0 +1,208 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, Timelike};
+use failure::format_err;
+
+use crate::interrupt::is_interrupted;
+use crate::result::Result;
+use crate::utils::parse_size;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Parse a transfer rate in bytes per second, such as `500K` or `5M`.
+pub fn parse_rate(rate: &str) -> Result<u64> {
+    parse_size(rate).map_err(|_| format_err!("Invalid rate <{}>", rate))
+}
+
+/// Token bucket shared by all transfers, allowing bursts of at most one second.
+pub struct RateLimiter {
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> RateLimiter {
+        RateLimiter {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take tokens for a number of bytes, waiting as long as it takes to stay
+    /// under the rate. Tokens may go negative so that waiting transfers queue up.
+    pub fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.updated);
+            let refill = elapsed.as_secs() as f64 * self.rate as f64
+                + f64::from(elapsed.subsec_micros()) * self.rate as f64 / 1_000_000.0;
+            bucket.tokens = (bucket.tokens + refill).min(self.rate as f64) - bytes as f64;
+            bucket.updated = now;
+            bucket.tokens
+        };
+        if wait < 0.0 {
+            thread::sleep(Duration::from_micros(
+                (-wait * 1_000_000.0 / self.rate as f64) as u64,
+            ));
+        }
+    }
+}
+
+/// Daily time window in which transfers are allowed, such as `22:00-06:00`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownloadWindow {
+    start: u32,
+    end: u32,
+}
+
+impl DownloadWindow {
+    /// Tell whether transfers are allowed at a time, in seconds since midnight.
+    pub fn contains(&self, time: u32) -> bool {
+        if self.start <= self.end {
+            self.start == self.end || (self.start <= time && time < self.end)
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Time to wait from a time, in seconds since midnight, until transfers are allowed.
+    pub fn wait_from(&self, time: u32) -> Duration {
+        if self.contains(time) {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs(u64::from(
+                (self.start + SECONDS_PER_DAY - time) % SECONDS_PER_DAY,
+            ))
+        }
+    }
+
+    /// Pause the current thread until transfers are allowed, in local time, or
+    /// until the user interrupts the program.
+    pub fn wait(&self) {
+        let wait = self.wait_from(Local::now().num_seconds_from_midnight());
+        if wait > Duration::from_secs(0) {
+            println!(
+                "Outside of the download window {}, pausing until {}",
+                self,
+                format_time(self.start)
+            );
+            let until = Instant::now() + wait;
+            while Instant::now() < until && !is_interrupted() {
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DownloadWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", format_time(self.start), format_time(self.end))
+    }
+}
+
+impl FromStr for DownloadWindow {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<DownloadWindow> {
+        let parse_time = |time: &str| -> Option<u32> {
+            let mut parts = time.trim().splitn(2, ':');
+            let hours = parts.next()?.parse::<u32>().ok()?;
+            let minutes = parts.next()?.parse::<u32>().ok()?;
+            if hours < 24 && minutes < 60 {
+                Some((hours * 60 + minutes) * 60)
+            } else {
+                None
+            }
+        };
+        let mut times = s.splitn(2, '-');
+        match (
+            times.next().and_then(parse_time),
+            times.next().and_then(parse_time),
+        ) {
+            (Some(start), Some(end)) => Ok(DownloadWindow { start, end }),
+            _ => Err(format_err!(
+                "Invalid download window <{}>, such as 22:00-06:00",
+                s
+            )),
+        }
+    }
+}
+
+fn format_time(time: u32) -> String {
+    format!("{:02}:{:02}", time / 3600, time / 60 % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(hours: u32, minutes: u32) -> u32 {
+        (hours * 60 + minutes) * 60
+    }
+
+    #[test]
+    fn parse_rates() {
+        assert_eq!(parse_rate("5M").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(parse_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_rate("1.5K").unwrap(), 1536);
+        assert_eq!(parse_rate("2048").unwrap(), 2048);
+        assert!(parse_rate("M").is_err());
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+
+    #[test]
+    fn rate_limiter_waits() {
+        let limiter = RateLimiter::new(100_000);
+        let start = Instant::now();
+
+        limiter.acquire(100_000);
+        limiter.acquire(20_000);
+
+        assert!(Instant::now().duration_since(start) >= Duration::from_millis(190));
+    }
+
+    #[test]
+    fn window_over_midnight() {
+        let window = "22:00-06:00".parse::<DownloadWindow>().unwrap();
+
+        assert!(window.contains(at(23, 30)));
+        assert!(window.contains(at(2, 0)));
+        assert!(!window.contains(at(6, 0)));
+        assert!(!window.contains(at(12, 0)));
+        assert_eq!(window.wait_from(at(2, 0)), Duration::from_secs(0));
+        assert_eq!(window.wait_from(at(21, 30)), Duration::from_secs(30 * 60));
+        assert_eq!(window.to_string(), "22:00-06:00");
+    }
+
+    #[test]
+    fn window_within_day() {
+        let window = "9:30-17:00".parse::<DownloadWindow>().unwrap();
+
+        assert!(window.contains(at(9, 30)));
+        assert!(!window.contains(at(17, 0)));
+        assert_eq!(
+            window.wait_from(at(17, 0)),
+            Duration::from_secs(u64::from(at(16, 30)))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_window() {
+        assert!("22:00".parse::<DownloadWindow>().is_err());
+        assert!("25:00-06:00".parse::<DownloadWindow>().is_err());
+        assert!("22-06".parse::<DownloadWindow>().is_err());
+    }
+}
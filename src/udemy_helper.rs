@@ -17,6 +17,17 @@ impl<'a> UdemyHelper<'a> {
         UdemyHelper { fs_helper }
     }
 
+    pub fn calculate_course_dir(&self, target_dir: &str, course_name: &str) -> Result<String> {
+        let mut path_buf = PathBuf::from(target_dir);
+        path_buf.push(course_name);
+        let path = String::from(
+            path_buf
+                .to_str()
+                .ok_or_else(|| format_err!("Could not obtain course_dir"))?,
+        );
+        Ok(path)
+    }
+
     pub fn calculate_target_dir(
         &self,
         target_dir: &str,
@@ -40,13 +51,16 @@ impl<'a> UdemyHelper<'a> {
 
     pub fn calculate_target_filename(&self, target_dir: &str, lecture: &Lecture) -> Result<String> {
         let mut path_buf = PathBuf::from(target_dir);
-        let extension = Path::new(lecture.filename.as_str()).extension().unwrap();
-        path_buf.push(format!(
-            "{:03} {}.{}",
+        let name = format!(
+            "{:03} {}",
             lecture.object_index,
-            sanitize(lecture.title.as_str()),
-            extension.to_string_lossy()
-        ));
+            sanitize(lecture.title.as_str())
+        );
+        // Files of `File` lectures may come without any extension.
+        match Path::new(lecture.filename.as_str()).extension() {
+            Some(extension) => path_buf.push(format!("{}.{}", name, extension.to_string_lossy())),
+            None => path_buf.push(name),
+        }
         let path = String::from(
             path_buf
                 .to_str()
@@ -55,10 +69,61 @@ impl<'a> UdemyHelper<'a> {
         Ok(path)
     }
 
+    /// Name of a supplementary asset of a lecture, kept next to the lecture.
+    pub fn calculate_asset_filename(
+        &self,
+        target_dir: &str,
+        lecture: &Lecture,
+        asset: &SupplementaryAsset,
+    ) -> Result<String> {
+        let mut path_buf = PathBuf::from(target_dir);
+        path_buf.push(format!(
+            "{:03} {}",
+            lecture.object_index,
+            sanitize(asset.title.as_str())
+        ));
+        Ok(path_buf
+            .to_str()
+            .ok_or_else(|| format_err!("Could not obtain target_dir"))?
+            .into())
+    }
+
     pub fn create_target_dir(&self, path: &str) -> Result<()> {
         self.fs_helper.create_dir_recursive(path)?;
         Ok(())
     }
+
+    pub fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.fs_helper.write_file(path, data)
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<String> {
+        self.fs_helper.read_file(path)
+    }
+
+    pub fn rename_file(&self, from: &str, to: &str) -> Result<()> {
+        self.fs_helper.rename_file(from, to)
+    }
+
+    pub fn create_new_file(&self, path: &str, data: &[u8]) -> Result<bool> {
+        self.fs_helper.create_new_file(path, data)
+    }
+
+    pub fn remove_file(&self, path: &str) -> Result<()> {
+        self.fs_helper.remove_file(path)
+    }
+
+    pub fn file_exists(&self, path: &str) -> bool {
+        self.fs_helper.file_exists(path)
+    }
+
+    pub fn list_dir(&self, path: &str) -> Result<Vec<String>> {
+        self.fs_helper.list_dir(path)
+    }
+
+    pub fn available_space(&self, path: &str) -> Result<u64> {
+        self.fs_helper.available_space(path)
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +136,30 @@ mod test_udemy_helper {
         fn create_dir_recursive(&self, _path: &str) -> Result<()> {
             Ok(())
         }
+        fn write_file(&self, _path: &str, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+        fn read_file(&self, _path: &str) -> Result<String> {
+            Ok("".into())
+        }
+        fn rename_file(&self, _from: &str, _to: &str) -> Result<()> {
+            Ok(())
+        }
+        fn create_new_file(&self, _path: &str, _data: &[u8]) -> Result<bool> {
+            Ok(true)
+        }
+        fn remove_file(&self, _path: &str) -> Result<()> {
+            Ok(())
+        }
+        fn file_exists(&self, _path: &str) -> bool {
+            false
+        }
+        fn list_dir(&self, _path: &str) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+        fn available_space(&self, _path: &str) -> Result<u64> {
+            Ok(0)
+        }
     }
 
     #[test]
@@ -78,6 +167,7 @@ mod test_udemy_helper {
         let chapter = Chapter {
             object_index: 23,
             title: "The Title".into(),
+            description: String::new(),
             lectures: vec![],
         };
 
@@ -89,10 +179,23 @@ mod test_udemy_helper {
         assert_eq!(actual.unwrap(), "./my-course/023 The Title");
     }
 
+    #[test]
+    fn calculate_course_dir() {
+        let fs_helper = MockFsHelper {};
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let actual = udemy_helper.calculate_course_dir("./", "my-course");
+
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), "./my-course");
+    }
+
     #[test]
     fn calculate_target_file() {
         let lecture = Lecture {
             has_video: true,
+            asset_type: "Video".into(),
+            duration: 147,
+            supplementary_assets: vec![],
             filename: "blah-blah.mp4".into(),
             id: 4321,
             object_index: 32,
@@ -106,4 +209,30 @@ mod test_udemy_helper {
 
         assert!(actual.is_ok());
     }
+
+    #[test]
+    fn calculate_asset_file() {
+        let lecture = Lecture {
+            has_video: true,
+            asset_type: "Video".into(),
+            duration: 147,
+            supplementary_assets: vec![],
+            filename: "blah-blah.mp4".into(),
+            id: 4321,
+            object_index: 32,
+            title: "The Lecture".into(),
+        };
+        let asset = SupplementaryAsset {
+            id: 1,
+            title: "exercise: flexbox.zip".into(),
+            asset_type: "File".into(),
+        };
+
+        let fs_helper = MockFsHelper {};
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+
+        let actual = udemy_helper.calculate_asset_filename("./", &lecture, &asset);
+
+        assert_eq!(actual.unwrap(), "./032 exercise_ flexbox.zip");
+    }
 }
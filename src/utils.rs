@@ -1,6 +1,10 @@
-use crate::result::Result;
 use failure::format_err;
+
+use crate::error::UdemyError;
+use crate::result::Result;
 use serde_json::Value;
+use std::path::Path;
+use std::time::Instant;
 
 /// Returns a cross-platform-filename-safe version of any string.
 ///
@@ -32,24 +36,83 @@ pub fn sanitize(component: &str) -> String {
     buf
 }
 
+/// Last component of a path, empty when there is none.
+pub fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Escape text for html and xml, in content as in attribute values.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn elapsed_millis(start: Instant) -> u64 {
+    let elapsed = Instant::now().duration_since(start);
+    elapsed.as_secs() * 1000u64 + u64::from(elapsed.subsec_millis())
+}
+
 pub fn calculate_download_speed(total: u64, elapsed: u64) -> f64 {
-    (total * 1000u64 / elapsed) as f64 / 1024.0 / 1024.0
+    (total * 1000u64 / elapsed.max(1)) as f64 / 1024.0 / 1024.0
+}
+
+/// Format a duration in seconds as `m:ss`, or `h:mm:ss` when over an hour.
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Parse a number of bytes with an optional `K`, `M` or `G` suffix (powers of
+/// 1024), such as `500K` or `20G`.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&size[..size.len() - 1], 1024),
+        Some('M') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    match number.trim().parse::<f64>() {
+        // Casting would saturate infinite and huge values silently.
+        Ok(number) if number > 0.0 && number * (multiplier as f64) < u64::MAX as f64 => {
+            Ok((number * multiplier as f64) as u64)
+        }
+        _ => Err(format_err!("Invalid size <{}>", size)),
+    }
 }
 
 pub fn json_get_string<'a, 'b>(value: &'a Value, key: &'b str) -> Result<&'a str> {
-    value
-        .get(key)
-        .ok_or_else(|| format_err!("Error parsing json ({})", key))?
+    Ok(json_get(value, key)?
         .as_str()
-        .ok_or_else(|| format_err!("Error parsing json ({})", key))
+        .ok_or_else(|| json_error(key, "not a string"))?)
 }
 
 pub fn json_get_u64(value: &Value, key: &str) -> Result<u64> {
-    value
-        .get(key)
-        .ok_or_else(|| format_err!("Error parsing json ({})", key))?
+    Ok(json_get(value, key)?
         .as_u64()
-        .ok_or_else(|| format_err!("Error parsing json ({})", key))
+        .ok_or_else(|| json_error(key, "not an unsigned integer"))?)
+}
+
+pub fn json_get<'a>(value: &'a Value, key: &str) -> Result<&'a Value> {
+    Ok(value
+        .get(key)
+        .ok_or_else(|| json_error(key, "missing field"))?)
+}
+
+pub fn json_error(path: &str, message: &str) -> UdemyError {
+    UdemyError::Parse {
+        path: path.into(),
+        message: message.into(),
+    }
 }
 
 #[cfg(test)]
@@ -74,10 +137,41 @@ mod test_udemy_helper {
         );
     }
 
+    #[test]
+    fn escape_markup() {
+        let actual = escape(r#"<b>"Flexbox" & Grid</b>"#);
+
+        assert_eq!(actual, "&lt;b&gt;&quot;Flexbox&quot; &amp; Grid&lt;/b&gt;");
+    }
+
     #[test]
     fn test_calculate_download_speed() {
         let actual = calculate_download_speed(1024u64 * 1024u64, 1000);
 
         assert_eq!(actual, 1.0);
     }
+
+    #[test]
+    fn format_duration_minutes() {
+        assert_eq!(format_duration(147), "2:27");
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("20G").unwrap(), 20 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5k").unwrap(), 1536);
+        assert_eq!(parse_size("2048").unwrap(), 2048);
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("big").is_err());
+        assert!(parse_size("inf").is_err());
+        assert!(parse_size("NaN").is_err());
+        assert!(parse_size("-5M").is_err());
+        assert!(parse_size("1e30").is_err());
+    }
+
+    #[test]
+    fn format_duration_hours() {
+        assert_eq!(format_duration(3 * 3600 + 5 * 60 + 9), "3:05:09");
+    }
 }
@@ -0,0 +1,396 @@
+use std::path::Path;
+
+use serde_derive::Serialize;
+
+use crate::model::*;
+use crate::playlist::relative_path;
+use crate::result::Result;
+use crate::udemy_helper::UdemyHelper;
+use crate::utils::{escape, file_name};
+
+/// Name of the viewer page in the course directory.
+pub const INDEX_FILENAME: &str = "index.html";
+
+/// Chapter as shown by the viewer.
+#[derive(Serialize, Debug)]
+pub struct ViewerChapter {
+    pub object_index: ObjectIndex,
+    pub title: String,
+    pub lectures: Vec<ViewerLecture>,
+}
+
+/// Lecture as shown by the viewer, with the paths of its files relative to the
+/// course directory, when they are there.
+#[derive(Serialize, Debug)]
+pub struct ViewerLecture {
+    pub id: LectureId,
+    pub object_index: ObjectIndex,
+    pub title: String,
+    pub duration: u64,
+    pub video: Option<String>,
+    pub audio: Option<String>,
+    pub article: Option<String>,
+    pub file: Option<String>,
+    pub captions: Vec<ViewerCaption>,
+    pub resources: Vec<ViewerResource>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ViewerCaption {
+    pub locale: String,
+    pub path: String,
+}
+
+/// Supplementary file of a lecture, linked when found in the chapter directory.
+#[derive(Serialize, Debug)]
+pub struct ViewerResource {
+    pub title: String,
+    pub path: Option<String>,
+}
+
+/// Describe a chapter out of the files found in its directory, named as
+/// downloads name them.
+pub fn viewer_chapter(
+    udemy_helper: &UdemyHelper,
+    chapter: &Chapter,
+    chapter_path: &str,
+    files: &[String],
+) -> Result<ViewerChapter> {
+    let chapter_dir = file_name(chapter_path);
+    let chapter_dir = chapter_dir.as_str();
+    let found = |name: &str| {
+        if files.iter().any(|file| file == name) {
+            Some(relative_path(&[chapter_dir, name]))
+        } else {
+            None
+        }
+    };
+    let mut lectures = Vec::with_capacity(chapter.lectures.len());
+    for lecture in chapter.lectures.iter() {
+        let filename = file_name(
+            udemy_helper
+                .calculate_target_filename(chapter_path, lecture)?
+                .as_str(),
+        );
+        let stem = Path::new(filename.as_str())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| filename.clone());
+        let prefix = format!("{}.", stem);
+        let captions = files
+            .iter()
+            .filter(|file| file.starts_with(prefix.as_str()) && file.ends_with(".vtt"))
+            .map(|file| ViewerCaption {
+                locale: file[prefix.len()..file.len() - ".vtt".len()].into(),
+                path: relative_path(&[chapter_dir, file.as_str()]),
+            })
+            .collect();
+        let mut resources = Vec::new();
+        for asset in lecture
+            .supplementary_assets
+            .iter()
+            .filter(|asset| asset.asset_type == "File")
+        {
+            let asset_filename =
+                udemy_helper.calculate_asset_filename(chapter_path, lecture, asset)?;
+            resources.push(ViewerResource {
+                title: asset.title.clone(),
+                path: found(file_name(asset_filename.as_str()).as_str()),
+            });
+        }
+        lectures.push(ViewerLecture {
+            id: lecture.id,
+            object_index: lecture.object_index,
+            title: lecture.title.clone(),
+            duration: lecture.duration,
+            video: found(format!("{}.mp4", stem).as_str()),
+            audio: found(format!("{}.m4a", stem).as_str()),
+            article: if lecture.asset_type == "Article" {
+                found(format!("{}.html", stem).as_str())
+            } else {
+                None
+            },
+            file: if lecture.asset_type == "File" {
+                found(filename.as_str())
+            } else {
+                None
+            },
+            captions,
+            resources,
+        });
+    }
+    Ok(ViewerChapter {
+        object_index: chapter.object_index,
+        title: chapter.title.clone(),
+        lectures,
+    })
+}
+
+/// Render the viewer page of a course. The watched state is kept in the local
+/// storage of the browser, under keys made of the course key.
+pub fn render_index(title: &str, key: &str, chapters: &[ViewerChapter]) -> Result<String> {
+    let data = serde_json::to_string(&serde_json::json!({
+        "title": title,
+        "key": key,
+        "chapters": chapters,
+    }))?
+    // Keeps the data from closing the script element.
+    .replace("</", "<\\/");
+    Ok(INDEX_TEMPLATE
+        .replace("{{TITLE}}", escape(title).as_str())
+        .replace("{{DATA}}", data.as_str()))
+}
+
+/// Standalone page of an article, its body being html.
+pub fn article_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; line-height: 1.5; }}</style>\n</head>\n<body>\n<h1>{}</h1>\n{}\n</body>\n</html>\n",
+        escape(title),
+        escape(title),
+        body
+    )
+}
+
+const INDEX_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { margin: 0; display: flex; height: 100vh; font-family: sans-serif; }
+nav { width: 22em; overflow-y: auto; background: #f7f8fa; border-right: 1px solid #d1d7dc; }
+nav h1 { font-size: 1.1em; margin: 1em; }
+nav h2 { font-size: 0.95em; margin: 0; padding: 0.8em 1em; background: #e9ebee; }
+nav ul { list-style: none; margin: 0; padding: 0; }
+nav li { display: flex; align-items: center; padding: 0.5em 1em; cursor: pointer; font-size: 0.9em; }
+nav li:hover { background: #e4e8eb; }
+nav li.current { background: #d1d7dc; }
+nav li.missing { color: #999; }
+nav li span { flex: 1; margin-left: 0.5em; }
+nav li small { color: #6a6f73; }
+main { flex: 1; overflow-y: auto; padding: 1em 2em; }
+main video { width: 100%; max-height: 75vh; background: #000; }
+main audio { width: 100%; }
+main iframe { width: 100%; height: 75vh; border: 1px solid #d1d7dc; }
+</style>
+</head>
+<body>
+<nav id="sidebar"></nav>
+<main id="content"></main>
+<script>
+const course = {{DATA}};
+const storageKey = id => "udemy-dl:" + course.key + ":" + id;
+const url = path => path.split("/").map(encodeURIComponent).join("/");
+const isWatched = lecture => localStorage.getItem(storageKey(lecture.id)) === "1";
+let current = null;
+
+function setWatched(lecture, watched) {
+  if (watched) {
+    localStorage.setItem(storageKey(lecture.id), "1");
+  } else {
+    localStorage.removeItem(storageKey(lecture.id));
+  }
+  renderSidebar();
+}
+
+function duration(seconds) {
+  return seconds > 0 ? Math.floor(seconds / 60) + ":" + String(seconds % 60).padStart(2, "0") : "";
+}
+
+function element(name, text) {
+  const node = document.createElement(name);
+  if (text !== undefined) {
+    node.textContent = text;
+  }
+  return node;
+}
+
+function renderSidebar() {
+  const sidebar = document.getElementById("sidebar");
+  sidebar.innerHTML = "";
+  sidebar.appendChild(element("h1", course.title));
+  for (const chapter of course.chapters) {
+    sidebar.appendChild(element("h2", chapter.object_index + ". " + chapter.title));
+    const list = element("ul");
+    for (const lecture of chapter.lectures) {
+      const item = element("li");
+      const checkbox = element("input");
+      checkbox.type = "checkbox";
+      checkbox.checked = isWatched(lecture);
+      checkbox.title = "Watched";
+      checkbox.onclick = event => {
+        event.stopPropagation();
+        setWatched(lecture, checkbox.checked);
+      };
+      item.appendChild(checkbox);
+      item.appendChild(element("span", lecture.object_index + ". " + lecture.title));
+      item.appendChild(element("small", duration(lecture.duration)));
+      if (!lecture.video && !lecture.audio && !lecture.article && !lecture.file) {
+        item.className = "missing";
+      }
+      if (current === lecture) {
+        item.className = "current";
+      }
+      item.onclick = () => show(lecture);
+      list.appendChild(item);
+    }
+    sidebar.appendChild(list);
+  }
+}
+
+function show(lecture) {
+  current = lecture;
+  location.hash = lecture.id;
+  const content = document.getElementById("content");
+  content.innerHTML = "";
+  content.appendChild(element("h2", lecture.title));
+  const media = lecture.video ? element("video") : lecture.audio ? element("audio") : null;
+  if (media) {
+    media.controls = true;
+    media.src = url(lecture.video || lecture.audio);
+    for (const caption of lecture.captions) {
+      const track = element("track");
+      track.kind = "subtitles";
+      track.srclang = caption.locale.split("_")[0];
+      track.label = caption.locale;
+      track.src = url(caption.path);
+      media.appendChild(track);
+    }
+    media.onended = () => setWatched(lecture, true);
+    content.appendChild(media);
+  } else if (lecture.article) {
+    const frame = element("iframe");
+    frame.src = url(lecture.article);
+    content.appendChild(frame);
+    setWatched(lecture, true);
+  } else if (lecture.file) {
+    const link = element("a", "Open " + lecture.file.split("/").pop());
+    link.href = url(lecture.file);
+    content.appendChild(link);
+  } else {
+    content.appendChild(element("p", "This lecture was not downloaded."));
+  }
+  if (lecture.resources.length > 0) {
+    content.appendChild(element("h3", "Resources"));
+    const list = element("ul");
+    for (const resource of lecture.resources) {
+      const item = element("li");
+      if (resource.path) {
+        const link = element("a", resource.title);
+        link.href = url(resource.path);
+        item.appendChild(link);
+      } else {
+        item.textContent = resource.title + " (not downloaded)";
+      }
+      list.appendChild(item);
+    }
+    content.appendChild(list);
+  }
+  renderSidebar();
+}
+
+const lectures = course.chapters.flatMap(chapter => chapter.lectures);
+const wanted = lectures.find(lecture => String(lecture.id) === location.hash.slice(1));
+const next = lectures.find(lecture => !isWatched(lecture));
+if (wanted || next || lectures[0]) {
+  show(wanted || next || lectures[0]);
+} else {
+  renderSidebar();
+}
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mocks::test::*;
+    use crate::test_data::test::*;
+
+    #[test]
+    fn describe_chapter() {
+        let fs_helper = MockFsHelper {};
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let mut course_content = make_test_course_content();
+        let chapter = &mut course_content.chapters[0];
+        let assets = &mut chapter.lectures[0].supplementary_assets;
+        assets.push(SupplementaryAsset {
+            id: 1,
+            title: "exercise.zip".into(),
+            asset_type: "File".into(),
+        });
+        assets.push(SupplementaryAsset {
+            id: 2,
+            title: "slides.pdf".into(),
+            asset_type: "File".into(),
+        });
+        assets.push(SupplementaryAsset {
+            id: 3,
+            title: "MDN".into(),
+            asset_type: "ExternalLink".into(),
+        });
+        let chapter_path = "~/Downloads/the-course/001 The Chapter";
+        let lecture = &chapter.lectures[0];
+        // Named as downloads name them.
+        let video = udemy_helper
+            .calculate_target_filename(chapter_path, lecture)
+            .unwrap();
+        let exercise = udemy_helper
+            .calculate_asset_filename(chapter_path, lecture, &lecture.supplementary_assets[0])
+            .unwrap();
+        let files: Vec<String> = vec![
+            file_name(video.as_str()),
+            "001 The Lecture.en_US.vtt".into(),
+            "001 The Lecture.fr_FR.vtt".into(),
+            "001 The Lecture.nfo".into(),
+            file_name(exercise.as_str()),
+        ];
+
+        let actual = viewer_chapter(&udemy_helper, chapter, chapter_path, &files).unwrap();
+
+        let lecture = &actual.lectures[0];
+        assert_eq!(
+            lecture.video,
+            Some("001 The Chapter/001 The Lecture.mp4".into())
+        );
+        assert_eq!(lecture.audio, None);
+        assert_eq!(lecture.captions.len(), 2);
+        assert_eq!(lecture.captions[0].locale, "en_US");
+        assert_eq!(
+            lecture.captions[1].path,
+            "001 The Chapter/001 The Lecture.fr_FR.vtt"
+        );
+        assert_eq!(lecture.resources.len(), 2);
+        assert_eq!(
+            lecture.resources[0].path,
+            Some("001 The Chapter/001 exercise.zip".into())
+        );
+        assert_eq!(lecture.resources[1].path, None);
+    }
+
+    #[test]
+    fn render_index_page() {
+        let chapters = vec![ViewerChapter {
+            object_index: 1,
+            title: "</script> & more".into(),
+            lectures: vec![],
+        }];
+
+        let actual = render_index("CSS <The Complete Guide>", "the-course", &chapters).unwrap();
+
+        assert!(actual.contains("<title>CSS &lt;The Complete Guide&gt;</title>"));
+        assert!(actual.contains("\"key\":\"the-course\""));
+        assert!(actual.contains("<\\/script> & more"));
+        assert!(!actual.contains("{{DATA}}"));
+    }
+
+    #[test]
+    fn render_article_page() {
+        let actual = article_page("Flexbox & Grid", "<p>Some <b>bold</b> text</p>");
+
+        assert!(actual.contains("<title>Flexbox &amp; Grid</title>"));
+        assert!(actual.contains("<p>Some <b>bold</b> text</p>"));
+    }
+}
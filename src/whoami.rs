@@ -0,0 +1,143 @@
+use std::any::Any;
+
+use failure::format_err;
+
+use crate::command::*;
+use crate::result::Result;
+use crate::summary::Summary;
+
+/// Show which account the access token belongs to.
+pub struct WhoAmI {}
+
+impl WhoAmI {
+    pub fn new() -> WhoAmI {
+        WhoAmI {}
+    }
+}
+
+impl Command for WhoAmI {
+    fn set_params(&mut self, _params: &dyn Any) {}
+
+    fn execute(&self, context: &CommandContext) -> Result<Summary> {
+        let user = context
+            .user
+            .as_ref()
+            .ok_or_else(|| format_err!("User should be authenticated for executing command"))?;
+        println!(
+            "Logged in as {} (id {}) on portal {} ({}.udemy.com)",
+            user.title, user.id, context.portal_name, context.portal_name
+        );
+        Ok(Summary::new())
+    }
+
+    fn needs_course_info(&self) -> bool {
+        false
+    }
+
+    fn shows_user(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::downloader::UdemyDownloader;
+    use crate::mocks::test::*;
+    use crate::model::*;
+    use crate::udemy_helper::UdemyHelper;
+
+    #[test]
+    fn whoami() {
+        let _mocks = lock_mocks();
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+
+        context.user = Some(User {
+            id: 1234,
+            title: "John Doe".into(),
+        });
+        let downloader = UdemyDownloader::new(&mut context);
+
+        let whoami = WhoAmI::new();
+
+        let result = downloader.execute(&whoami);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn whoami_without_user() {
+        let _mocks = lock_mocks();
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::new(
+            "https://www.udemy.com/course/css-the-complete-guide-incl-flexbox-grid-sass",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        )
+        .unwrap();
+        let downloader = UdemyDownloader::new(&mut context);
+
+        let whoami = WhoAmI::new();
+
+        let result = downloader.execute(&whoami);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn whoami_without_course() {
+        let _mocks = lock_mocks();
+        unsafe {
+            GETS_AS_JSON_URL = Some(vec![]);
+        }
+        let fs_helper = MockFsHelper {};
+
+        let mock_http_client = MockHttpClient {};
+        let mock_parser = MockParser::new();
+        let udemy_helper = UdemyHelper::new(&fs_helper);
+        let auth = Auth::with_token("blah");
+
+        let mut context = CommandContext::for_portal(
+            "acme",
+            &mock_http_client,
+            &mock_parser,
+            &udemy_helper,
+            auth,
+        );
+        let mut downloader = UdemyDownloader::new(&mut context);
+
+        assert!(downloader.authenticate(false, false).is_ok());
+        assert!(downloader.execute(&WhoAmI::new()).is_ok());
+        unsafe {
+            if let Some(ref gaj) = GETS_AS_JSON_URL {
+                assert_eq!(
+                    gaj[0],
+                    "https://acme.udemy.com/api-2.0/users/me/?fields[user]=id,title"
+                );
+            }
+        }
+    }
+}